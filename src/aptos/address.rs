@@ -0,0 +1,163 @@
+use std::{fmt, str::FromStr};
+
+use eyre::{ensure, Context, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Адрес аккаунта Aptos.
+///
+/// Разбор как в Aptos CLI: префикс `0x` не обязателен, короткая форма
+/// дополняется нулями слева (`0x1` == `0x000…01`), регистр не важен.
+/// Сериализуется в длинной форме с префиксом `0x`, в BCS - 32 байтами.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub(crate) struct AccountAddress([u8; AccountAddress::LENGTH]);
+
+impl AccountAddress {
+    pub(crate) const LENGTH: usize = 32;
+    pub(crate) const ZERO: Self = Self([0; Self::LENGTH]);
+    pub(crate) const ONE: Self = Self::special(1);
+
+    pub(crate) const fn new(bytes: [u8; Self::LENGTH]) -> Self {
+        Self(bytes)
+    }
+
+    /// Зарезервированный адрес `0x0`..=`0xf`, например `0xa`.
+    pub(crate) const fn special(value: u8) -> Self {
+        let mut bytes = [0; Self::LENGTH];
+        bytes[Self::LENGTH - 1] = value;
+        Self(bytes)
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8; Self::LENGTH] {
+        &self.0
+    }
+
+    /// Зарезервированные адреса `0x0`..=`0xf`.
+    pub(crate) fn is_special(self) -> bool {
+        self.0[..Self::LENGTH - 1].iter().all(|byte| *byte == 0) && self.0[Self::LENGTH - 1] < 0x10
+    }
+
+    /// `0x` и 64 hex символа.
+    pub(crate) fn to_long_string(self) -> String {
+        format!("0x{}", hex::encode(self.0))
+    }
+
+    /// Каноничная форма из AIP-40: короткая для зарезервированных адресов, иначе длинная.
+    pub(crate) fn to_standard_string(self) -> String {
+        if self.is_special() {
+            format!("0x{:x}", self.0[Self::LENGTH - 1])
+        } else {
+            self.to_long_string()
+        }
+    }
+}
+
+impl FromStr for AccountAddress {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let hex = value.strip_prefix("0x").unwrap_or(value);
+        ensure!(!hex.is_empty(), "Пустой адрес {value:?}");
+        ensure!(
+            hex.len() <= Self::LENGTH * 2,
+            "Адрес {value:?} длиннее {} символов",
+            Self::LENGTH * 2
+        );
+
+        let mut bytes = [0; Self::LENGTH];
+        hex::decode_to_slice(format!("{hex:0>64}"), &mut bytes)
+            .with_context(|| format!("Адрес {value:?} не является hex строкой"))?;
+        Ok(Self(bytes))
+    }
+}
+
+impl fmt::Display for AccountAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_long_string())
+    }
+}
+
+impl fmt::Debug for AccountAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Serialize for AccountAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_long_string())
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return <[u8; Self::LENGTH]>::deserialize(deserializer).map(Self);
+        }
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|err: eyre::Report| de::Error::custom(format!("{err:#}")))
+    }
+}
+
+#[test]
+fn test_account_address_parse() -> Result<()> {
+    let one = AccountAddress::ONE;
+    for value in [
+        "0x1",
+        "1",
+        "0x01",
+        "0x0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+    ] {
+        assert_eq!(value.parse::<AccountAddress>()?, one, "{value}");
+    }
+
+    let alice: AccountAddress =
+        "0x5E67137F218CA70760FF0A7D792CB4286B5A80FD81C66191D5A0412E161EC0EA".parse()?;
+    assert_eq!(
+        alice.to_string(),
+        "0x5e67137f218ca70760ff0a7d792cb4286b5a80fd81c66191d5a0412e161ec0ea"
+    );
+    assert_eq!(alice.to_standard_string(), alice.to_long_string());
+    assert_eq!(format!("{one:?}"), one.to_string());
+    assert_eq!(one.to_standard_string(), "0x1");
+    assert_eq!(
+        "0x45".parse::<AccountAddress>()?.to_standard_string(),
+        format!("0x{:0>64}", "45")
+    );
+    assert_eq!(
+        "0".repeat(64).parse::<AccountAddress>()?,
+        AccountAddress::ZERO
+    );
+
+    for value in ["", "0x", "0xZZ", "0x 1", "0x0x1", &"1".repeat(65)] {
+        assert!(value.parse::<AccountAddress>().is_err(), "{value:?}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_account_address_serde() -> Result<()> {
+    let address: AccountAddress = serde_json::from_str("\"0x45\"")?;
+    assert_eq!(
+        serde_json::to_string(&address)?,
+        format!("\"0x{:0>64}\"", "45")
+    );
+    assert_eq!(
+        serde_json::from_str::<AccountAddress>(&format!("\"{:0>64}\"", "45"))?,
+        address
+    );
+    assert!(serde_json::from_str::<AccountAddress>("\"0xZZ\"").is_err());
+
+    let bytes = bcs::to_bytes(&address)?;
+    assert_eq!(bytes, address.as_bytes());
+    assert_eq!(bcs::from_bytes::<AccountAddress>(&bytes)?, address);
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+
+use eyre::{bail, Context, ContextCompat, Result};
+use futures::try_join;
+use serde_json::Value;
+use sha3::{Digest, Sha3_256};
+use tracing::{debug, instrument};
+
+use super::{aptos_error, AccountAddress, AptosClient, ViewRequest};
+
+/// Ресурс с балансом APT до перехода на Fungible Asset.
+pub(crate) const COIN_STORE: &str = "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>";
+/// Ресурс primary fungible store.
+pub(crate) const FUNGIBLE_STORE: &str = "0x1::fungible_asset::FungibleStore";
+/// View функция с суммарным балансом: CoinStore и парный fungible store.
+pub(crate) const COIN_BALANCE_VIEW: &str = "0x1::coin::balance";
+const APTOS_COIN: &str = "0x1::aptos_coin::AptosCoin";
+/// Адрес метаданных APT как Fungible Asset.
+const APT_METADATA: AccountAddress = AccountAddress::special(0xa);
+/// Суффикс для адресов объектов, производных от адреса пользователя.
+const OBJECT_FROM_USER_SCHEME: u8 = 0xfc;
+
+/// Откуда взят баланс.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BalanceSource {
+    /// View функция `0x1::coin::balance`.
+    View,
+    /// Сумма CoinStore и primary fungible store.
+    Stores,
+    /// Аккаунт не найден ни в одном источнике.
+    Missing,
+}
+
+/// Баланс APT по каждому источнику. `None` - источник не содержит баланса аккаунта.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct BalanceReport {
+    pub(crate) coin_store: Option<u64>,
+    pub(crate) fungible_store: Option<u64>,
+    pub(crate) view: Option<u64>,
+}
+
+impl BalanceReport {
+    /// Итоговый баланс и источник: view функция, если доступна, иначе сумма ресурсов.
+    pub(crate) fn total(&self) -> (u64, BalanceSource) {
+        match (self.view, self.stores()) {
+            (Some(view), _) => (view, BalanceSource::View),
+            (None, Some(stores)) => (stores, BalanceSource::Stores),
+            (None, None) => (0, BalanceSource::Missing),
+        }
+    }
+
+    /// Расхождение между view функцией и суммой ресурсов.
+    pub(crate) fn discrepancy(&self) -> Option<String> {
+        let view = self.view?;
+        let stores = self.stores().unwrap_or_default();
+        (view != stores).then(|| {
+            format!(
+                "{COIN_BALANCE_VIEW}: {view}, {COIN_STORE}: {:?} + {FUNGIBLE_STORE}: {:?}",
+                self.coin_store, self.fungible_store
+            )
+        })
+    }
+
+    /// Ошибка, если источники баланса `account` не совпадают.
+    pub(crate) fn ensure_consistent(&self, account: &AccountAddress) -> Result<()> {
+        if let Some(discrepancy) = self.discrepancy() {
+            bail!("Источники баланса {account} не совпадают: {discrepancy}");
+        }
+        Ok(())
+    }
+
+    fn stores(&self) -> Option<u64> {
+        match (self.coin_store, self.fungible_store) {
+            (None, None) => None,
+            (coin, fungible) => Some(
+                coin.unwrap_or_default()
+                    .saturating_add(fungible.unwrap_or_default()),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for BalanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (total, source) = self.total();
+        write!(
+            f,
+            "{total} ({source:?}). coin_store: {:?}, fungible_store: {:?}, view: {:?}",
+            self.coin_store, self.fungible_store, self.view
+        )
+    }
+}
+
+/// Адрес primary fungible store аккаунта для APT:
+/// `sha3_256(owner | metadata | 0xFC)`.
+pub(crate) fn primary_store_address(owner: &AccountAddress) -> AccountAddress {
+    let mut hasher = Sha3_256::new();
+    hasher.update(owner.as_bytes());
+    hasher.update(APT_METADATA.as_bytes());
+    hasher.update([OBJECT_FROM_USER_SCHEME]);
+    AccountAddress::new(hasher.finalize().into())
+}
+
+/// Баланс APT из всех источников на одной версии ledger, чтобы параллельные
+/// транзакции не давали ложных расхождений.
+#[instrument(level = "debug")]
+pub(crate) async fn balance_report(
+    base_url: &str,
+    account: &AccountAddress,
+) -> Result<BalanceReport> {
+    let client = AptosClient::new(base_url);
+    let version = Some(client.ledger_info().await?.ledger_version);
+    let primary_store = primary_store_address(account);
+
+    let (coin_store, fungible_store, view) = try_join!(
+        client.resource_at(account, COIN_STORE, version),
+        client.resource_at(&primary_store, FUNGIBLE_STORE, version),
+        view_balance(&client, account, version),
+    )?;
+    let report = BalanceReport {
+        coin_store: coin_store
+            .map(|resource| parse_amount(&resource.data, "/coin/value"))
+            .transpose()?,
+        fungible_store: fungible_store
+            .map(|resource| parse_amount(&resource.data, "/balance"))
+            .transpose()?,
+        view,
+    };
+    debug!("{account}: {report}");
+    Ok(report)
+}
+
+/// Баланс через view функцию. `None`, если нода не смогла её выполнить,
+/// например, у аккаунта нет ни CoinStore, ни fungible store на старых версиях фреймворка.
+async fn view_balance(
+    client: &AptosClient,
+    account: &AccountAddress,
+    ledger_version: Option<u64>,
+) -> Result<Option<u64>> {
+    let request = ViewRequest::new(COIN_BALANCE_VIEW)
+        .type_argument(APTOS_COIN)
+        .argument(account);
+    match client.view_at(&request, ledger_version).await {
+        Ok(values) => parse_amount(&Value::Array(values), "/0").map(Some),
+        Err(err) => match aptos_error(&err) {
+            Some(error) => {
+                debug!("{COIN_BALANCE_VIEW} для {account}: {error}");
+                Ok(None)
+            }
+            None => Err(err),
+        },
+    }
+}
+
+/// Сумма в формате Aptos REST API: `u64` строкой.
+fn parse_amount(body: &Value, pointer: &str) -> Result<u64> {
+    body.pointer(pointer)
+        .and_then(Value::as_str)
+        .with_context(|| format!("Нет {pointer} в ответе {body:#}"))?
+        .parse()
+        .with_context(|| format!("Неудалось преобразовать {pointer} в u64: {body:#}"))
+}
+
+#[test]
+fn test_balance_report() {
+    let report = |coin_store, fungible_store, view| BalanceReport {
+        coin_store,
+        fungible_store,
+        view,
+    };
+
+    assert_eq!(
+        report(None, None, None).total(),
+        (0, BalanceSource::Missing)
+    );
+    assert_eq!(
+        report(Some(5), None, None).total(),
+        (5, BalanceSource::Stores)
+    );
+    assert_eq!(
+        report(Some(5), Some(3), Some(8)).total(),
+        (8, BalanceSource::View)
+    );
+    assert_eq!(report(Some(5), Some(3), Some(8)).discrepancy(), None);
+    assert_eq!(report(None, Some(3), Some(3)).discrepancy(), None);
+    assert!(report(Some(5), None, Some(8)).discrepancy().is_some());
+    assert!(report(None, None, Some(1)).discrepancy().is_some());
+    assert_eq!(report(Some(5), None, None).discrepancy(), None);
+
+    let err = report(Some(5), None, Some(8))
+        .ensure_consistent(&AccountAddress::ONE)
+        .unwrap_err();
+    assert!(err.to_string().contains(COIN_BALANCE_VIEW), "{err}");
+    assert!(report(Some(5), Some(3), Some(8))
+        .ensure_consistent(&AccountAddress::ONE)
+        .is_ok());
+}
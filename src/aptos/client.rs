@@ -0,0 +1,507 @@
+use std::{fmt, str::FromStr};
+
+use eyre::{Context, ContextCompat, Result};
+use reqwest::{header::CONTENT_TYPE, Client, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::{debug, instrument};
+use tracing_test::traced_test;
+
+use super::{transaction::SignedTransaction, AccountAddress, COIN_BALANCE_VIEW, COIN_STORE};
+
+/// Content-Type подписанной транзакции в BCS.
+pub(crate) const SIGNED_TRANSACTION_BCS: &str = "application/x.aptos.signed_transaction+bcs";
+
+/// Клиент Aptos REST API (`/v1`).
+///
+/// Числа `u64`, которые API отдаёт строками, разбираются в числа. Ошибки API
+/// возвращаются как [`AptosError`] внутри `eyre::Report`, см. [`aptos_error`].
+#[derive(Debug, Clone)]
+pub(crate) struct AptosClient {
+    base_url: String,
+    http: Client,
+}
+
+/// Ошибка в ответе Aptos REST API.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct AptosError {
+    #[serde(skip)]
+    pub(crate) status: u16,
+    pub(crate) message: String,
+    pub(crate) error_code: String,
+    pub(crate) vm_error_code: Option<u64>,
+}
+
+impl fmt::Display for AptosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{} {}] {}", self.status, self.error_code, self.message)
+    }
+}
+
+impl std::error::Error for AptosError {}
+
+/// Ошибка Aptos REST API, если запрос завершился ею.
+pub(crate) fn aptos_error(report: &eyre::Report) -> Option<&AptosError> {
+    report.downcast_ref()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct LedgerInfo {
+    pub(crate) chain_id: u8,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) epoch: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) ledger_version: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) oldest_ledger_version: u64,
+    /// Время последнего блока в микросекундах.
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) ledger_timestamp: u64,
+    pub(crate) node_role: String,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) oldest_block_height: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) block_height: u64,
+    pub(crate) git_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct AccountInfo {
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) sequence_number: u64,
+    pub(crate) authentication_key: String,
+}
+
+/// Move ресурс аккаунта.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct Resource {
+    #[serde(rename = "type")]
+    pub(crate) resource_type: String,
+    pub(crate) data: Value,
+}
+
+/// Move модуль аккаунта.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct Module {
+    pub(crate) bytecode: String,
+    pub(crate) abi: Option<Value>,
+}
+
+/// Транзакция любого типа. Общие поля разобраны, остальные доступны в `other`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct Transaction {
+    /// `user_transaction`, `block_metadata_transaction`, `pending_transaction`, ...
+    #[serde(rename = "type")]
+    pub(crate) transaction_type: String,
+    pub(crate) hash: String,
+    /// Нет у транзакций, ожидающих исполнения.
+    #[serde(default, deserialize_with = "from_str_option")]
+    pub(crate) version: Option<u64>,
+    pub(crate) success: Option<bool>,
+    pub(crate) vm_status: Option<String>,
+    pub(crate) sender: Option<AccountAddress>,
+    #[serde(default, deserialize_with = "from_str_option")]
+    pub(crate) sequence_number: Option<u64>,
+    #[serde(default, deserialize_with = "from_str_option")]
+    pub(crate) gas_used: Option<u64>,
+    #[serde(default, deserialize_with = "from_str_option")]
+    pub(crate) gas_unit_price: Option<u64>,
+    #[serde(default)]
+    pub(crate) events: Vec<Event>,
+    #[serde(flatten)]
+    pub(crate) other: Map<String, Value>,
+}
+
+impl Transaction {
+    pub(crate) fn is_pending(&self) -> bool {
+        self.transaction_type == "pending_transaction"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct Block {
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) block_height: u64,
+    pub(crate) block_hash: String,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) block_timestamp: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) first_version: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) last_version: u64,
+    /// Есть только при запросе с `with_transactions=true`.
+    pub(crate) transactions: Option<Vec<Transaction>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct Event {
+    pub(crate) guid: EventGuid,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) sequence_number: u64,
+    #[serde(rename = "type")]
+    pub(crate) event_type: String,
+    pub(crate) data: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct EventGuid {
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) creation_number: u64,
+    pub(crate) account_address: AccountAddress,
+}
+
+/// Запрос к view функции.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct ViewRequest {
+    pub(crate) function: String,
+    pub(crate) type_arguments: Vec<String>,
+    pub(crate) arguments: Vec<Value>,
+}
+
+impl AptosClient {
+    pub(crate) fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            http: Client::new(),
+        }
+    }
+
+    pub(crate) async fn ledger_info(&self) -> Result<LedgerInfo> {
+        self.get("").await
+    }
+
+    pub(crate) async fn account(&self, account: &AccountAddress) -> Result<AccountInfo> {
+        self.get(&format!("/accounts/{account}")).await
+    }
+
+    pub(crate) async fn resources(&self, account: &AccountAddress) -> Result<Vec<Resource>> {
+        self.get(&format!("/accounts/{account}/resources")).await
+    }
+
+    /// Ресурс аккаунта. `None`, если нет аккаунта или ресурса.
+    pub(crate) async fn resource(
+        &self,
+        account: &AccountAddress,
+        resource_type: &str,
+    ) -> Result<Option<Resource>> {
+        self.resource_at(account, resource_type, None).await
+    }
+
+    /// Ресурс аккаунта на версии `ledger_version`. Без версии - на последней.
+    pub(crate) async fn resource_at(
+        &self,
+        account: &AccountAddress,
+        resource_type: &str,
+        ledger_version: Option<u64>,
+    ) -> Result<Option<Resource>> {
+        not_found_as_none(
+            self.get(&format!(
+                "/accounts/{account}/resource/{resource_type}{}",
+                at_version(ledger_version)
+            ))
+            .await,
+        )
+    }
+
+    pub(crate) async fn modules(&self, account: &AccountAddress) -> Result<Vec<Module>> {
+        self.get(&format!("/accounts/{account}/modules")).await
+    }
+
+    pub(crate) async fn transaction_by_hash(&self, hash: &str) -> Result<Transaction> {
+        self.get(&format!("/transactions/by_hash/{hash}")).await
+    }
+
+    pub(crate) async fn transaction_by_version(&self, version: u64) -> Result<Transaction> {
+        self.get(&format!("/transactions/by_version/{version}"))
+            .await
+    }
+
+    pub(crate) async fn block_by_height(
+        &self,
+        height: u64,
+        with_transactions: bool,
+    ) -> Result<Block> {
+        self.get(&format!(
+            "/blocks/by_height/{height}?with_transactions={with_transactions}"
+        ))
+        .await
+    }
+
+    /// События по `creation_number` из GUID хэндла.
+    pub(crate) async fn events_by_creation_number(
+        &self,
+        account: &AccountAddress,
+        creation_number: u64,
+        start: Option<u64>,
+        limit: Option<u16>,
+    ) -> Result<Vec<Event>> {
+        self.get(&format!(
+            "/accounts/{account}/events/{creation_number}{}",
+            page(start, limit)
+        ))
+        .await
+    }
+
+    /// События хэндла `field` ресурса `event_handle`, например
+    /// `0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>` и `deposit_events`.
+    pub(crate) async fn events_by_event_handle(
+        &self,
+        account: &AccountAddress,
+        event_handle: &str,
+        field: &str,
+        start: Option<u64>,
+        limit: Option<u16>,
+    ) -> Result<Vec<Event>> {
+        self.get(&format!(
+            "/accounts/{account}/events/{event_handle}/{field}{}",
+            page(start, limit)
+        ))
+        .await
+    }
+
+    /// Отправка подписанной транзакции в BCS. Возвращает `pending_transaction`.
+    pub(crate) async fn submit(&self, transaction: &SignedTransaction) -> Result<Transaction> {
+        let body = bcs::to_bytes(transaction).context("Не удалось сериализовать транзакцию")?;
+        send(
+            &self.http,
+            self.http
+                .post(self.url("/transactions"))
+                .header(CONTENT_TYPE, SIGNED_TRANSACTION_BCS)
+                .body(body),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Транзакция {} от {} не принята",
+                transaction.raw_txn.payload.0, transaction.raw_txn.sender
+            )
+        })
+    }
+
+    pub(crate) async fn view(&self, request: &ViewRequest) -> Result<Vec<Value>> {
+        self.view_at(request, None).await
+    }
+
+    /// View функция на версии `ledger_version`. Без версии - на последней.
+    pub(crate) async fn view_at(
+        &self,
+        request: &ViewRequest,
+        ledger_version: Option<u64>,
+    ) -> Result<Vec<Value>> {
+        let url = self.url(&format!("/view{}", at_version(ledger_version)));
+        send(&self.http, self.http.post(url).json(request))
+            .await
+            .with_context(|| format!("view {}", request.function))
+    }
+
+    pub(crate) async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        send(&self.http, self.http.get(self.url(path))).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1{path}", self.base_url)
+    }
+}
+
+/// Запрос к Aptos REST API или faucet. Ответ не 200/202 - [`AptosError`].
+#[instrument(level = "debug", skip_all)]
+pub(crate) async fn send<T: DeserializeOwned>(http: &Client, request: RequestBuilder) -> Result<T> {
+    let request = request.build()?;
+    let url = request.url().to_string();
+    let response = http
+        .execute(request)
+        .await
+        .with_context(|| format!("При обращении к {url} возникла ошибка"))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .with_context(|| format!("Не удалось прочитать ответ {url}"))?;
+    debug!("{url}: {status:?}");
+
+    if status != StatusCode::OK && status != StatusCode::ACCEPTED {
+        let error = match serde_json::from_str::<AptosError>(&body) {
+            Ok(error) => AptosError {
+                status: status.as_u16(),
+                ..error
+            },
+            Err(_) => AptosError {
+                status: status.as_u16(),
+                message: body,
+                error_code: String::new(),
+                vm_error_code: None,
+            },
+        };
+        return Err(eyre::Report::new(error).wrap_err(format!("Ошибка Aptos REST API. Url: {url}")));
+    }
+    serde_json::from_str(&body)
+        .with_context(|| format!("Ответ {url} не соответствует схеме: {body}"))
+}
+
+impl ViewRequest {
+    pub(crate) fn new(function: &str) -> Self {
+        Self {
+            function: function.into(),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn type_argument(mut self, type_argument: &str) -> Self {
+        self.type_arguments.push(type_argument.into());
+        self
+    }
+
+    pub(crate) fn argument(mut self, argument: impl Serialize) -> Self {
+        self.arguments.push(json!(argument));
+        self
+    }
+}
+
+/// Ошибка 404 превращается в `None`.
+pub(crate) fn not_found_as_none<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if aptos_error(&err).is_some_and(|err| err.status == 404) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn at_version(ledger_version: Option<u64>) -> String {
+    ledger_version.map_or_else(String::new, |version| format!("?ledger_version={version}"))
+}
+
+fn page(start: Option<u64>, limit: Option<u16>) -> String {
+    let params = [
+        start.map(|start| format!("start={start}")),
+        limit.map(|limit| format!("limit={limit}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    if params.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", params.join("&"))
+    }
+}
+
+/// Число, которое Aptos REST API передаёт строкой.
+pub(crate) fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+fn from_str_option<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[test]
+fn test_aptos_types() -> Result<()> {
+    let transaction: Transaction = serde_json::from_value(json!({
+        "type": "user_transaction",
+        "version": "42",
+        "hash": "0xab",
+        "success": true,
+        "vm_status": "Executed successfully",
+        "sender": "0x1",
+        "sequence_number": "7",
+        "gas_used": "10",
+        "max_gas_amount": "100",
+        "events": [{
+            "guid": { "creation_number": "2", "account_address": "0x1" },
+            "sequence_number": "3",
+            "type": "0x1::coin::DepositEvent",
+            "data": { "amount": "5" },
+        }],
+    }))?;
+    assert_eq!(transaction.version, Some(42));
+    assert_eq!(transaction.sequence_number, Some(7));
+    assert_eq!(transaction.sender, Some(AccountAddress::ONE));
+    assert_eq!(transaction.events[0].guid.creation_number, 2);
+    assert_eq!(transaction.gas_used, Some(10));
+    assert_eq!(transaction.other["max_gas_amount"], "100");
+    assert!(!transaction.is_pending());
+
+    let pending: Transaction = serde_json::from_value(json!({
+        "type": "pending_transaction",
+        "hash": "0xab",
+        "sender": "0x1",
+        "sequence_number": "7",
+    }))?;
+    assert!(pending.is_pending());
+    assert_eq!(pending.version, None);
+
+    let block: Block = serde_json::from_value(json!({
+        "block_height": "5",
+        "block_hash": "0xcd",
+        "block_timestamp": "1000",
+        "first_version": "10",
+        "last_version": "12",
+        "transactions": null,
+    }))?;
+    assert_eq!((block.first_version, block.last_version), (10, 12));
+
+    assert_eq!(page(None, None), "");
+    assert_eq!(page(Some(1), Some(10)), "?start=1&limit=10");
+    Ok(())
+}
+
+#[ignore]
+#[tokio::test]
+#[traced_test]
+async fn test_aptos_client() -> Result<()> {
+    let client = super::client();
+    let info = client.ledger_info().await?;
+    debug!("{info:?}");
+
+    let block = client.block_by_height(info.block_height, true).await?;
+    assert_eq!(block.block_height, info.block_height);
+    let first = &block.transactions.context("Блок без транзакций")?[0];
+    let by_version = client.transaction_by_version(block.first_version).await?;
+    assert_eq!(&by_version, first);
+    let by_hash = client.transaction_by_hash(&first.hash).await?;
+    assert_eq!(by_hash.version, Some(block.first_version));
+
+    let alice = super::profile("alice")?.account;
+    let account = client.account(&alice).await?;
+    debug!("alice: {account:?}");
+    let resources = client.resources(&alice).await?;
+    assert!(resources
+        .iter()
+        .any(|resource| resource.resource_type == "0x1::account::Account"));
+    assert!(!client.modules(&AccountAddress::ONE).await?.is_empty());
+
+    let events = client
+        .events_by_event_handle(&alice, COIN_STORE, "deposit_events", None, Some(10))
+        .await?;
+    if let Some(event) = events.first() {
+        let by_creation_number = client
+            .events_by_creation_number(&alice, event.guid.creation_number, Some(0), Some(1))
+            .await?;
+        assert_eq!(by_creation_number[0].event_type, event.event_type);
+    }
+
+    let balance = client
+        .view(
+            &ViewRequest::new(COIN_BALANCE_VIEW)
+                .type_argument("0x1::aptos_coin::AptosCoin")
+                .argument(alice),
+        )
+        .await?;
+    debug!("balance: {balance:?}");
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use eyre::{eyre, Context, ContextCompat, Result};
+use fs4::fs_std::FileExt;
+use serde_yaml::{Mapping, Value};
+use tracing::debug;
+
+use super::{aptos_url, faucet_url, LocalAccount};
+
+/// Переменная окружения с файлом, в который сохраняются созданные аккаунты.
+const ACCOUNTS_ENV: &str = "TEST_L2_ACCOUNTS";
+
+/// Новые аккаунты со случайными ключами, свои у каждого теста.
+///
+/// Если задан файл профилей, каждый аккаунт дописывается в него в формате
+/// `.aptos/config.yaml`, чтобы потом посмотреть его через Aptos CLI.
+#[derive(Debug, Clone)]
+pub(crate) struct AccountFactory {
+    /// Начало имён профилей, обычно имя теста.
+    prefix: String,
+    profile_file: Option<PathBuf>,
+}
+
+impl AccountFactory {
+    /// Файл профилей берётся из `TEST_L2_ACCOUNTS`.
+    pub(crate) fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            profile_file: env::var_os(ACCOUNTS_ENV).map(PathBuf::from),
+        }
+    }
+
+    pub(crate) fn persist_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.profile_file = Some(path.into());
+        self
+    }
+
+    pub(crate) fn account(&self) -> Result<LocalAccount> {
+        let account = LocalAccount::generate();
+        debug!("{}: {}", self.profile_name(&account), account.address());
+        if let Some(path) = &self.profile_file {
+            self.persist(path, &account)?;
+        }
+        Ok(account)
+    }
+
+    pub(crate) fn accounts<const N: usize>(&self) -> Result<[LocalAccount; N]> {
+        (0..N)
+            .map(|_| self.account())
+            .collect::<Result<Vec<_>>>()?
+            .try_into()
+            .map_err(|_| eyre!("Создано не {N} аккаунтов"))
+    }
+
+    /// Имя профиля: префикс и начало адреса.
+    pub(crate) fn profile_name(&self, account: &LocalAccount) -> String {
+        format!(
+            "{}-{}",
+            self.prefix,
+            &hex::encode(account.address().as_bytes())[..8]
+        )
+    }
+
+    /// Запись профиля в файл. Остальные профили и поля файла сохраняются.
+    fn persist(&self, path: &PathBuf, account: &LocalAccount) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Не удалось открыть {path:?}"))?;
+        // Блокировка снимается при закрытии файла
+        file.lock_exclusive()
+            .with_context(|| format!("Не удалось заблокировать {path:?}"))?;
+
+        let mut yaml = String::new();
+        file.read_to_string(&mut yaml)?;
+        let mut config = match serde_yaml::from_str::<Option<Mapping>>(&yaml)
+            .with_context(|| format!("Не валидный yaml в {path:?}"))?
+        {
+            Some(config) => config,
+            None => Mapping::new(),
+        };
+        let profiles = config
+            .entry("profiles".into())
+            .or_insert_with(|| Value::Mapping(Mapping::new()))
+            .as_mapping_mut()
+            .with_context(|| format!("profiles в {path:?} не является словарём"))?;
+
+        let profile = [
+            ("private_key", account.private_key()),
+            ("public_key", account.public_key()),
+            // Aptos CLI записывает адрес без 0x
+            ("account", hex::encode(account.address().as_bytes())),
+            ("rest_url", aptos_url()),
+            ("faucet_url", faucet_url()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect::<Mapping>();
+        profiles.insert(self.profile_name(account).into(), profile.into());
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(serde_yaml::to_string(&config)?.as_bytes())
+            .with_context(|| format!("Ошибка при записи профиля в {path:?}"))
+    }
+}
+
+#[test]
+fn test_account_factory() -> Result<()> {
+    use super::profile::AptosConfig;
+    use std::fs;
+
+    let path = env::temp_dir().join(format!("test_l2_{}.yaml", rand::random::<u64>()));
+    fs::write(&path, "---\nprofiles:\n  default:\n    account: \"0x1\"\n")?;
+    let factory = AccountFactory::new("factory").persist_to(&path);
+
+    let [first, second] = factory.accounts()?;
+    let third = factory.account()?;
+    assert_ne!(first.address(), second.address());
+    for account in [&first, &second, &third] {
+        assert_eq!(account.authentication_key(), account.address());
+    }
+
+    let config = AptosConfig::load(&path)?;
+    fs::remove_file(&path)?;
+    assert_eq!(config.profiles.len(), 4, "Профили дописываются");
+    assert!(config.profiles.contains_key("default"));
+    for account in [&first, &second, &third] {
+        let profile = config.profile(&factory.profile_name(account))?;
+        assert_eq!(profile.account, account.address());
+        assert_eq!(
+            profile.local_account()?.private_key(),
+            account.private_key()
+        );
+        assert_eq!(profile.public_key.as_deref(), Some(&*account.public_key()));
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use eyre::{ensure, Context, Result};
+use futures::future::try_join_all;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, instrument};
+use tracing_test::traced_test;
+
+use super::{client::send, AccountAddress, AptosClient, Transaction};
+
+/// Faucet локальной ноды.
+pub(crate) const FAUCET_URL: &str = "http://localhost:8081";
+
+/// Клиент faucet локальной ноды.
+#[derive(Debug, Clone)]
+pub(crate) struct FaucetClient {
+    url: String,
+    http: Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct FundResponse {
+    txn_hashes: Vec<String>,
+}
+
+impl FaucetClient {
+    pub(crate) fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: Client::new(),
+        }
+    }
+
+    /// Пополнение аккаунта на `amount` octas. Возвращает хэши транзакций faucet.
+    #[instrument(level = "debug", skip(self))]
+    pub(crate) async fn fund(&self, account: &AccountAddress, amount: u64) -> Result<Vec<String>> {
+        let request = self
+            .http
+            .post(format!("{}/fund", self.url))
+            .json(&json!({ "address": account, "amount": amount }));
+        let hashes = send::<FundResponse>(&self.http, request)
+            .await
+            .with_context(|| format!("Faucet не пополнил {account}"))?
+            .txn_hashes;
+        debug!("{account}: {hashes:?}");
+        Ok(hashes)
+    }
+
+    /// Пополнение и ожидание выполнения транзакций faucet.
+    pub(crate) async fn fund_and_wait(
+        &self,
+        aptos: &AptosClient,
+        account: &AccountAddress,
+        amount: u64,
+    ) -> Result<Vec<Transaction>> {
+        let hashes = self.fund(account, amount).await?;
+        try_join_all(hashes.iter().map(|hash| aptos.wait_for_transaction(hash))).await
+    }
+}
+
+/// Faucet и engine API зачисляют одинаковую сумму на новые аккаунты одинаково.
+#[traced_test]
+#[tokio::test]
+async fn test_faucet_and_deposit() -> Result<()> {
+    use super::{aptos_url, balance_report, client, faucet, AccountFactory};
+    use crate::{
+        deposit::wait_for_deltas,
+        engine_client::{new_client, MvEngine, RequestEngine},
+        engine_url,
+        jwt::get_jwt,
+        SLOTS,
+    };
+
+    let engine = new_client(&engine_url(), get_jwt().await)?;
+    let aptos = client();
+    let [minted, deposited] = AccountFactory::new("faucet")
+        .accounts()?
+        .map(|account| account.address());
+    let amount = 5_000_000;
+
+    let transactions = faucet().fund_and_wait(&aptos, &minted, amount).await?;
+    ensure!(!transactions.is_empty(), "Faucet не отправил транзакций");
+    let request = RequestEngine::builder()
+        .slot(|slot| slot.deposit(deposited, amount))
+        .build(&engine, &SLOTS)
+        .await?;
+    engine.engine_apply_all(&request).await?;
+
+    let report = wait_for_deltas(
+        &aptos_url(),
+        &[(minted, 0), (deposited, 0)].into(),
+        &[(minted, amount.into()), (deposited, amount.into())].into(),
+    )
+    .await?;
+    debug!("\n{report}");
+
+    let [minted, deposited] = [
+        balance_report(&aptos_url(), &minted).await?,
+        balance_report(&aptos_url(), &deposited).await?,
+    ];
+    debug!("faucet: {minted}");
+    debug!("deposit: {deposited}");
+    assert_eq!(minted.total().0, deposited.total().0);
+    assert_eq!(minted.discrepancy(), None);
+    assert_eq!(deposited.discrepancy(), None);
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use eyre::Result;
+use futures::future::try_join_all;
+use tokio::test;
+use tracing::debug;
+use tracing_test::traced_test;
+
+use crate::mock;
+
+pub(crate) use address::AccountAddress;
+pub(crate) use balance::{
+    balance_report, primary_store_address, BalanceReport, BalanceSource, COIN_BALANCE_VIEW,
+    COIN_STORE, FUNGIBLE_STORE,
+};
+pub(crate) use client::{
+    aptos_error, from_str, AptosClient, Event, LedgerInfo, Transaction, ViewRequest,
+    SIGNED_TRANSACTION_BCS,
+};
+pub(crate) use factory::AccountFactory;
+pub(crate) use faucet::{FaucetClient, FAUCET_URL};
+pub(crate) use profile::{builtin_profile, profile, profiles, AptosConfig};
+pub(crate) use transaction::{
+    EntryFunction, LocalAccount, RawTransaction, SignedTransaction, Variant, GAS_UNIT_PRICE,
+    MAX_GAS_AMOUNT,
+};
+
+mod address;
+mod balance;
+mod client;
+mod factory;
+mod faucet;
+mod profile;
+mod transaction;
+
+const URL: &str = "http://localhost:8080";
+
+/// Адрес Aptos REST API: `rest_url` профиля по умолчанию или mock при `TEST_L2_MOCK=1`.
+/// Без конфига или с невалидным конфигом - локальная нода.
+pub(crate) fn aptos_url() -> String {
+    if mock::enabled() {
+        mock::shared().aptos_url.clone()
+    } else {
+        profiles()
+            .ok()
+            .and_then(AptosConfig::default_profile)
+            .map_or(URL, |profile| &profile.rest_url)
+            .to_string()
+    }
+}
+
+/// Адрес faucet: `faucet_url` профиля по умолчанию или mock при `TEST_L2_MOCK=1`.
+/// Без конфига или с невалидным конфигом - локальная нода.
+pub(crate) fn faucet_url() -> String {
+    if mock::enabled() {
+        mock::shared().faucet_url.clone()
+    } else {
+        profiles()
+            .ok()
+            .and_then(AptosConfig::default_profile)
+            .and_then(|profile| profile.faucet_url.as_deref())
+            .unwrap_or(FAUCET_URL)
+            .to_string()
+    }
+}
+
+/// Адрес аккаунта по имени профиля (`alice`, `bob`, `eve`) или hex-адрес.
+pub(crate) fn resolve_account(name: &str) -> Result<AccountAddress> {
+    profiles()?.resolve(name)
+}
+
+/// Клиент Aptos REST API по адресу [`aptos_url`].
+pub(crate) fn client() -> AptosClient {
+    AptosClient::new(aptos_url())
+}
+
+/// Клиент faucet по адресу [`faucet_url`].
+pub(crate) fn faucet() -> FaucetClient {
+    FaucetClient::new(faucet_url())
+}
+
+pub(crate) async fn balance(account: &AccountAddress) -> Result<u64> {
+    balance_at(&aptos_url(), account).await
+}
+
+// $ aptos account list --query balance --account <ACCOUNT>
+// $ curl --request GET --url https://api.devnet.aptoslabs.com/v1/accounts/<__ADDRESS__>/resource/<__RESOURCE_TYPE__>
+/// Баланс APT с учётом аккаунтов, перешедших на Fungible Asset. Источники описаны в [`BalanceReport`].
+/// Ошибка, если источники не совпадают.
+pub(crate) async fn balance_at(base_url: &str, account: &AccountAddress) -> Result<u64> {
+    let report = balance_report(base_url, account).await?;
+    report.ensure_consistent(account)?;
+    Ok(report.total().0)
+}
+
+#[ignore]
+#[test]
+#[traced_test]
+async fn test_balance() -> Result<()> {
+    let profiles = profiles()?.profiles.values().collect::<Vec<_>>();
+    let tasks = profiles
+        .iter()
+        .map(|profile| balance(&profile.account))
+        .collect::<Vec<_>>();
+    profiles
+        .iter()
+        .zip(try_join_all(tasks).await?)
+        .for_each(|(profile, balance)| {
+            debug!("{} {}: {balance}", profile.name, profile.account);
+        });
+
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use eyre::{eyre, Context, ContextCompat, Result};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use super::{AccountAddress, LocalAccount, URL};
+use crate::mock;
+
+/// Путь к конфигу Aptos CLI относительно каталога проекта.
+const CONFIG_PATH: &str = ".aptos/config.yaml";
+/// Переменная окружения с явным путём к конфигу.
+const CONFIG_ENV: &str = "APTOS_CONFIG";
+/// Профили локальной ноды, если конфиг Aptos CLI не найден.
+const DEFAULT_CONFIG: &str = r#"
+profiles:
+  alice:
+    private_key: "0x170e9218f1b8ccb44f9877ce423364021756fa438207af1f594e955e3131b0fd"
+    public_key: "0xdad2adbcf857ccf8f610d0a44f19a82f74f24e1142effd03bad571a5dc86f7a2"
+    account: 5e67137f218ca70760ff0a7d792cb4286b5a80fd81c66191d5a0412e161ec0ea
+    rest_url: "http://localhost:8080"
+    faucet_url: "http://localhost:8081"
+  bob:
+    private_key: "0xcae6621dc96fbe5d09cbfe925bbfffec8a88b7765d275561f9fa03c24b660cf8"
+    public_key: "0x9eea1f4c7e33bb33c4f588ee13ee6948467fcf6a2f91eef8a0a434ae6ca9a60d"
+    account: 12ebe3e67d11259a82646bffc7caff724ab61e9cbefc2c80df255986351f135c
+    rest_url: "http://localhost:8080"
+    faucet_url: "http://localhost:8081"
+  eve:
+    private_key: "0x5f0af78aad7bfd6445c0d9b179f92c7b1d6561acc4bb4d4dcf077caa1fbc7026"
+    public_key: "0xefe6d3a3bf426c4576cdf1b5617120d53c1192043c2a37917666dfc7ba331555"
+    account: 04228e4f14a6f2f8d202f1bbe151aaadf1105d1fc3c9c0dc1804f5773c34d62b
+    rest_url: "http://localhost:8080"
+    faucet_url: "http://localhost:8081"
+"#;
+
+/// Профили из найденного конфига Aptos CLI, см. [`AptosConfig::find`].
+/// Ошибка конфига возвращается из [`profiles`], а не ломает все тесты.
+static PROFILES: LazyLock<Result<AptosConfig>> = LazyLock::new(|| {
+    let config = match AptosConfig::find() {
+        Some(path) => AptosConfig::load(&path),
+        None => Ok(BUILTIN.clone()),
+    };
+    if let Err(err) = &config {
+        warn!("{err:#}");
+    }
+    config
+});
+
+/// Профили локальной ноды из [`DEFAULT_CONFIG`].
+static BUILTIN: LazyLock<AptosConfig> = LazyLock::new(|| {
+    AptosConfig::parse(DEFAULT_CONFIG).expect("Встроенный конфиг соответствует схеме")
+});
+
+/// Профиль Aptos CLI.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct Profile {
+    /// Имя профиля - ключ в `profiles`.
+    #[serde(skip)]
+    pub(crate) name: String,
+    pub(crate) private_key: Option<String>,
+    pub(crate) public_key: Option<String>,
+    pub(crate) account: AccountAddress,
+    #[serde(default = "default_rest_url")]
+    pub(crate) rest_url: String,
+    pub(crate) faucet_url: Option<String>,
+}
+
+/// `.aptos/config.yaml`. Поля, кроме профилей, не используются.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct AptosConfig {
+    pub(crate) profiles: BTreeMap<String, Profile>,
+}
+
+impl Profile {
+    /// Аккаунт с ключом профиля для подписи транзакций.
+    pub(crate) fn local_account(&self) -> Result<LocalAccount> {
+        let private_key = self
+            .private_key
+            .as_deref()
+            .with_context(|| format!("У профиля {} нет private_key", self.name))?;
+        // Новые версии Aptos CLI добавляют префикс схемы ключа (AIP-80)
+        let private_key = private_key
+            .strip_prefix("ed25519-priv-")
+            .unwrap_or(private_key);
+        LocalAccount::new(self.account, private_key)
+            .with_context(|| format!("Ключ профиля {}", self.name))
+    }
+}
+
+impl AptosConfig {
+    /// Путь из `APTOS_CONFIG`, иначе `.aptos/config.yaml` в каталоге проекта.
+    ///
+    /// Глобальный `~/.aptos/config.yaml` обычно указывает на devnet или testnet,
+    /// поэтому используется, только если `APTOS_CONFIG` явно указывает на него.
+    pub(crate) fn find() -> Option<PathBuf> {
+        if let Some(path) = env::var_os(CONFIG_ENV) {
+            return Some(path.into());
+        }
+        Some(Path::new(env!("CARGO_MANIFEST_DIR")).join(CONFIG_PATH)).filter(|path| path.is_file())
+    }
+
+    /// Встроенные профили `alice`, `bob` и `eve` локальной ноды.
+    pub(crate) fn builtin() -> &'static Self {
+        &BUILTIN
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        debug!("Профили Aptos: {}", path.display());
+        let yaml = fs::read_to_string(path)
+            .with_context(|| format!("Не удалось прочитать {}", path.display()))?;
+        Self::parse(&yaml).with_context(|| format!("Конфиг {}", path.display()))
+    }
+
+    pub(crate) fn parse(yaml: &str) -> Result<Self> {
+        let mut config: Self =
+            serde_yaml::from_str(yaml).context("Конфиг Aptos CLI не соответствует схеме")?;
+        for (name, profile) in &mut config.profiles {
+            profile.name.clone_from(name);
+        }
+        Ok(config)
+    }
+
+    pub(crate) fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles.get(name).with_context(|| {
+            format!(
+                "Нет профиля {name:?}. Профили: {:?}",
+                self.profiles.keys().collect::<Vec<_>>()
+            )
+        })
+    }
+
+    /// Адрес аккаунта по имени профиля или hex-адрес.
+    pub(crate) fn resolve(&self, name: &str) -> Result<AccountAddress> {
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.account),
+            None => name
+                .parse()
+                .with_context(|| format!("Неизвестный аккаунт {name:?}")),
+        }
+    }
+
+    /// Профиль `default`, иначе первый по имени.
+    pub(crate) fn default_profile(&self) -> Option<&Profile> {
+        self.profiles
+            .get("default")
+            .or_else(|| self.profiles.values().next())
+    }
+}
+
+/// Профили для тестов на ноде: встроенные при `TEST_L2_MOCK=1`,
+/// иначе из найденного конфига Aptos CLI.
+pub(crate) fn profiles() -> Result<&'static AptosConfig> {
+    if mock::enabled() {
+        return Ok(AptosConfig::builtin());
+    }
+    PROFILES.as_ref().map_err(|err| eyre!("{err:#}"))
+}
+
+/// Профиль из конфига Aptos CLI по имени, например `profile("alice")`.
+pub(crate) fn profile(name: &str) -> Result<Profile> {
+    profiles()?.profile(name).cloned()
+}
+
+/// Встроенный профиль по имени. Для unit и mock тестов, которые не должны
+/// зависеть от конфига пользователя.
+pub(crate) fn builtin_profile(name: &str) -> Result<Profile> {
+    AptosConfig::builtin().profile(name).cloned()
+}
+
+fn default_rest_url() -> String {
+    URL.to_string()
+}
+
+#[test]
+fn test_aptos_config() -> Result<()> {
+    let config = AptosConfig::parse(DEFAULT_CONFIG)?;
+    assert_eq!(&config, AptosConfig::builtin());
+    let alice = config.profile("alice")?;
+    assert_eq!(alice.name, "alice");
+    assert_eq!(alice.faucet_url.as_deref(), Some("http://localhost:8081"));
+    assert_eq!(alice.local_account()?.authentication_key(), alice.account);
+    assert_eq!(config.default_profile(), Some(alice));
+    assert!(config.profile("mallory").is_err());
+    assert_eq!(config.resolve("bob")?, config.profile("bob")?.account);
+    assert_eq!(config.resolve("0x1")?, AccountAddress::ONE);
+    assert!(config.resolve("mallory").is_err());
+
+    // Формат Aptos CLI 4.x: network, префиксы ключей, без rest_url
+    let config = AptosConfig::parse(
+        r#"
+---
+profiles:
+  default:
+    network: Devnet
+    private_key: "ed25519-priv-0x170e9218f1b8ccb44f9877ce423364021756fa438207af1f594e955e3131b0fd"
+    public_key: "ed25519-pub-0xdad2adbcf857ccf8f610d0a44f19a82f74f24e1142effd03bad571a5dc86f7a2"
+    account: 5e67137f218ca70760ff0a7d792cb4286b5a80fd81c66191d5a0412e161ec0ea
+  observer:
+    account: "0x1"
+"#,
+    )?;
+    let default = config.profile("default")?;
+    assert_eq!(default.rest_url, URL);
+    assert_eq!(
+        default.local_account()?.authentication_key(),
+        default.account
+    );
+    assert_eq!(config.default_profile(), Some(default));
+    assert!(config.profile("observer")?.local_account().is_err());
+
+    assert!(AptosConfig::parse("profiles:\n  bad:\n    account: 0xZZ\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_find_aptos_config() -> Result<()> {
+    let original = env::var_os(CONFIG_ENV);
+    // Встроенные профили: параллельные тесты могут загрузить конфиг, пока переменная задана
+    let path = env::temp_dir().join(format!("test_l2_{}.yaml", rand::random::<u64>()));
+    fs::write(&path, DEFAULT_CONFIG)?;
+
+    env::set_var(CONFIG_ENV, &path);
+    let explicit = AptosConfig::find();
+    env::remove_var(CONFIG_ENV);
+    let implicit = AptosConfig::find();
+    if let Some(original) = original {
+        env::set_var(CONFIG_ENV, original);
+    }
+
+    assert_eq!(explicit, Some(path));
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join(CONFIG_PATH);
+    assert_eq!(implicit, Some(project).filter(|path| path.is_file()));
+    if let Some(home) = env::var_os("HOME") {
+        assert_ne!(
+            implicit,
+            Some(Path::new(&home).join(CONFIG_PATH)),
+            "~/.aptos только через APTOS_CONFIG"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,519 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use eyre::{ensure, Context, ContextCompat, Result};
+use serde::{
+    de::{self, EnumAccess, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use sha3::{Digest, Sha3_256};
+use tracing::{debug, instrument};
+use tracing_test::traced_test;
+
+use super::{client::not_found_as_none, AccountAddress, AptosClient, Transaction};
+use crate::wait::{Check, Wait};
+
+/// Запас газа по умолчанию. Создание аккаунта получателя стоит около 1000 единиц.
+pub(crate) const MAX_GAS_AMOUNT: u64 = 10_000;
+/// Минимальная цена газа в octas.
+pub(crate) const GAS_UNIT_PRICE: u64 = 100;
+/// Через сколько транзакция перестаёт быть валидной.
+const EXPIRATION: Duration = Duration::from_secs(60);
+/// Сколько ждать выполнения отправленной транзакции.
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Транзакция до подписи. Поля в порядке BCS сериализации Aptos.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RawTransaction {
+    pub(crate) sender: AccountAddress,
+    pub(crate) sequence_number: u64,
+    /// `TransactionPayload::EntryFunction`.
+    pub(crate) payload: Variant<2, EntryFunction>,
+    pub(crate) max_gas_amount: u64,
+    pub(crate) gas_unit_price: u64,
+    pub(crate) expiration_timestamp_secs: u64,
+    pub(crate) chain_id: u8,
+}
+
+/// Вызов `entry fun`. Аргументы уже сериализованы в BCS.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct EntryFunction {
+    pub(crate) module: ModuleId,
+    pub(crate) function: String,
+    /// `TypeTag::Struct`: других типовых аргументов тесты не используют.
+    pub(crate) ty_args: Vec<Variant<7, StructTag>>,
+    pub(crate) args: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ModuleId {
+    pub(crate) address: AccountAddress,
+    pub(crate) name: String,
+}
+
+/// Тип структуры без типовых параметров, например `0x1::aptos_coin::AptosCoin`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct StructTag {
+    pub(crate) address: AccountAddress,
+    pub(crate) module: String,
+    pub(crate) name: String,
+    pub(crate) type_args: Vec<Variant<7, StructTag>>,
+}
+
+/// Подписанная транзакция с `TransactionAuthenticator::Ed25519`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SignedTransaction {
+    pub(crate) raw_txn: RawTransaction,
+    pub(crate) authenticator: Variant<0, Ed25519Authenticator>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Ed25519Authenticator {
+    pub(crate) public_key: Vec<u8>,
+    pub(crate) signature: Vec<u8>,
+}
+
+/// Вариант BCS enum с фиксированным номером `INDEX`.
+///
+/// Остальные варианты тестам не нужны, поэтому enum целиком не описывается.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Variant<const INDEX: u32, T>(pub(crate) T);
+
+impl<const INDEX: u32, T: fmt::Debug> fmt::Debug for Variant<INDEX, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<const INDEX: u32, T: Serialize> Serialize for Variant<INDEX, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_variant("Variant", INDEX, "", &self.0)
+    }
+}
+
+impl<'de, const INDEX: u32, T: Deserialize<'de>> Deserialize<'de> for Variant<INDEX, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct VariantVisitor<const INDEX: u32, T>(PhantomData<T>);
+
+        impl<'de, const INDEX: u32, T: Deserialize<'de>> Visitor<'de> for VariantVisitor<INDEX, T> {
+            type Value = Variant<INDEX, T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "enum variant {INDEX}")
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                let (index, variant) = data.variant::<u32>()?;
+                if index != INDEX {
+                    return Err(de::Error::custom(format!(
+                        "Неподдерживаемый вариант {index}, ожидался {INDEX}"
+                    )));
+                }
+                variant.newtype_variant().map(Variant)
+            }
+        }
+
+        deserializer.deserialize_enum("Variant", &[], VariantVisitor(PhantomData))
+    }
+}
+
+impl StructTag {
+    pub(crate) fn new(address: AccountAddress, module: &str, name: &str) -> Self {
+        Self {
+            address,
+            module: module.into(),
+            name: name.into(),
+            type_args: Vec::new(),
+        }
+    }
+}
+
+impl EntryFunction {
+    pub(crate) fn new(address: AccountAddress, module: &str, function: &str) -> Self {
+        Self {
+            module: ModuleId {
+                address,
+                name: module.into(),
+            },
+            function: function.into(),
+            ty_args: Vec::new(),
+            args: Vec::new(),
+        }
+    }
+
+    pub(crate) fn ty_arg(mut self, tag: StructTag) -> Self {
+        self.ty_args.push(Variant(tag));
+        self
+    }
+
+    pub(crate) fn arg(mut self, value: &impl Serialize) -> Result<Self> {
+        self.args
+            .push(bcs::to_bytes(value).context("Не удалось сериализовать аргумент")?);
+        Ok(self)
+    }
+
+    /// `0x1::aptos_account::transfer`: перевод APT, создаёт аккаунт получателя.
+    pub(crate) fn transfer(to: AccountAddress, amount: u64) -> Result<Self> {
+        Self::new(AccountAddress::ONE, "aptos_account", "transfer")
+            .arg(&to)?
+            .arg(&amount)
+    }
+}
+
+impl fmt::Display for EntryFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}::{}::{}",
+            self.module.address.to_standard_string(),
+            self.module.name,
+            self.function
+        )
+    }
+}
+
+impl RawTransaction {
+    /// Сообщение для подписи: `sha3_256("APTOS::RawTransaction") | bcs(raw)`.
+    pub(crate) fn signing_message(&self) -> Result<Vec<u8>> {
+        let mut message = Sha3_256::digest(b"APTOS::RawTransaction").to_vec();
+        message.extend(bcs::to_bytes(self).context("Не удалось сериализовать транзакцию")?);
+        Ok(message)
+    }
+}
+
+impl SignedTransaction {
+    /// Проверка подписи и того, что ключ соответствует `authentication_key`.
+    pub(crate) fn verify(&self, authentication_key: &AccountAddress) -> Result<()> {
+        let Ed25519Authenticator {
+            public_key,
+            signature,
+        } = &self.authenticator.0;
+        let public_key = VerifyingKey::from_bytes(
+            public_key
+                .as_slice()
+                .try_into()
+                .context("Публичный ключ не 32 байта")?,
+        )
+        .context("Невалидный публичный ключ")?;
+        ensure!(
+            authentication_key_of(&public_key) == *authentication_key,
+            "Ключ не соответствует authentication_key {authentication_key}"
+        );
+        let signature = Signature::from_slice(signature).context("Подпись не 64 байта")?;
+        public_key
+            .verify(&self.raw_txn.signing_message()?, &signature)
+            .context("Неверная подпись")
+    }
+
+    /// Хэш как у ноды: `sha3_256(sha3_256("APTOS::Transaction") | 0 | bcs(signed))`,
+    /// где `0` - вариант `Transaction::UserTransaction`.
+    pub(crate) fn hash(&self) -> Result<String> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(Sha3_256::digest(b"APTOS::Transaction"));
+        hasher.update(
+            bcs::to_bytes(&Variant::<0, _>(self)).context("Не удалось сериализовать транзакцию")?,
+        );
+        Ok(format!("0x{}", hex::encode(hasher.finalize())))
+    }
+}
+
+/// Аккаунт с приватным ключом ed25519.
+#[derive(Clone)]
+pub(crate) struct LocalAccount {
+    address: AccountAddress,
+    key: SigningKey,
+}
+
+impl fmt::Debug for LocalAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalAccount")
+            .field("address", &self.address)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LocalAccount {
+    /// Приватный ключ в hex, с префиксом `0x` или без.
+    pub(crate) fn new(address: AccountAddress, private_key: &str) -> Result<Self> {
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(
+            private_key.strip_prefix("0x").unwrap_or(private_key),
+            &mut bytes,
+        )
+        .context("Приватный ключ не является 32 байтами в hex")?;
+        Ok(Self {
+            address,
+            key: SigningKey::from_bytes(&bytes),
+        })
+    }
+
+    /// Новый аккаунт со случайным ключом. Адрес равен `authentication_key`.
+    pub(crate) fn generate() -> Self {
+        Self::from_key(SigningKey::from_bytes(&rand::random()))
+    }
+
+    /// Адрес из `authentication_key`, как у аккаунтов, созданных Aptos CLI.
+    pub(crate) fn from_private_key(private_key: &str) -> Result<Self> {
+        Ok(Self::from_key(
+            Self::new(AccountAddress::ZERO, private_key)?.key,
+        ))
+    }
+
+    fn from_key(key: SigningKey) -> Self {
+        Self {
+            address: authentication_key_of(&key.verifying_key()),
+            key,
+        }
+    }
+
+    pub(crate) fn address(&self) -> AccountAddress {
+        self.address
+    }
+
+    /// Приватный ключ в формате Aptos CLI: `0x` и hex.
+    pub(crate) fn private_key(&self) -> String {
+        format!("0x{}", hex::encode(self.key.to_bytes()))
+    }
+
+    pub(crate) fn public_key(&self) -> String {
+        format!("0x{}", hex::encode(self.key.verifying_key().to_bytes()))
+    }
+
+    /// `authentication_key` ключа аккаунта. Совпадает с адресом, если ключ не ротировали.
+    pub(crate) fn authentication_key(&self) -> AccountAddress {
+        authentication_key_of(&self.key.verifying_key())
+    }
+
+    pub(crate) fn sign(&self, raw_txn: RawTransaction) -> Result<SignedTransaction> {
+        let signature = self.key.sign(&raw_txn.signing_message()?);
+        Ok(SignedTransaction {
+            raw_txn,
+            authenticator: Variant(Ed25519Authenticator {
+                public_key: self.key.verifying_key().to_bytes().to_vec(),
+                signature: signature.to_bytes().to_vec(),
+            }),
+        })
+    }
+}
+
+/// `sha3_256(public_key | 0x00)`, где `0x00` - схема Ed25519.
+fn authentication_key_of(public_key: &VerifyingKey) -> AccountAddress {
+    let mut hasher = Sha3_256::new();
+    hasher.update(public_key.as_bytes());
+    hasher.update([0]);
+    AccountAddress::new(hasher.finalize().into())
+}
+
+impl AptosClient {
+    /// Подпись, отправка и ожидание успешного выполнения `payload` от имени `account`.
+    #[instrument(level = "debug", skip(self), fields(payload = %payload))]
+    pub(crate) async fn execute(
+        &self,
+        account: &LocalAccount,
+        payload: EntryFunction,
+    ) -> Result<Transaction> {
+        let sequence_number = not_found_as_none(self.account(&account.address).await)?
+            .map(|info| info.sequence_number)
+            .unwrap_or_default();
+        let info = self.ledger_info().await?;
+        let expiration = SystemTime::now().duration_since(UNIX_EPOCH)? + EXPIRATION;
+        let signed = account.sign(RawTransaction {
+            sender: account.address,
+            sequence_number,
+            payload: Variant(payload),
+            max_gas_amount: MAX_GAS_AMOUNT,
+            gas_unit_price: GAS_UNIT_PRICE,
+            expiration_timestamp_secs: expiration.as_secs(),
+            chain_id: info.chain_id,
+        })?;
+
+        let pending = self.submit(&signed).await?;
+        debug!("{}: {}", account.address, pending.hash);
+        self.wait_for_transaction(&pending.hash).await
+    }
+
+    /// Перевод APT через `0x1::aptos_account::transfer`.
+    pub(crate) async fn transfer(
+        &self,
+        from: &LocalAccount,
+        to: AccountAddress,
+        amount: u64,
+    ) -> Result<Transaction> {
+        self.execute(from, EntryFunction::transfer(to, amount)?)
+            .await
+    }
+
+    /// Ожидание выполнения транзакции. Ошибка, если транзакция выполнилась неуспешно.
+    pub(crate) async fn wait_for_transaction(&self, hash: &str) -> Result<Transaction> {
+        // 404, пока транзакция не попала в mempool ноды
+        let transaction = Wait::timeout(TRANSACTION_TIMEOUT)
+            .until(
+                &format!("выполнение транзакции {hash}"),
+                || async {
+                    Ok(
+                        match not_found_as_none(self.transaction_by_hash(hash).await)? {
+                            Some(transaction) if !transaction.is_pending() => {
+                                Check::Ready(transaction)
+                            }
+                            Some(_) => Check::Pending("pending_transaction".to_string()),
+                            None => Check::Pending("транзакция не найдена".to_string()),
+                        },
+                    )
+                },
+            )
+            .await?;
+        ensure!(
+            transaction.success == Some(true),
+            "Транзакция {hash} выполнилась с ошибкой: {}",
+            transaction.vm_status.unwrap_or_default()
+        );
+        Ok(transaction)
+    }
+}
+
+#[test]
+fn test_raw_transaction_bcs() -> Result<()> {
+    let raw = RawTransaction {
+        sender: AccountAddress::special(2),
+        sequence_number: 1,
+        payload: Variant(
+            EntryFunction::new(AccountAddress::ONE, "coin", "transfer")
+                .ty_arg(StructTag::new(
+                    AccountAddress::ONE,
+                    "aptos_coin",
+                    "AptosCoin",
+                ))
+                .arg(&AccountAddress::special(3))?
+                .arg(&300u64)?,
+        ),
+        max_gas_amount: 2,
+        gas_unit_price: 3,
+        expiration_timestamp_secs: 4,
+        chain_id: 5,
+    };
+
+    let address = |value: u8| AccountAddress::special(value).as_bytes().to_vec();
+    let string = |value: &str| [vec![value.len() as u8], value.as_bytes().to_vec()].concat();
+    let expected = [
+        address(2),
+        1u64.to_le_bytes().to_vec(),
+        // EntryFunction
+        vec![2],
+        address(1),
+        string("coin"),
+        string("transfer"),
+        // ty_args: один TypeTag::Struct
+        vec![1, 7],
+        address(1),
+        string("aptos_coin"),
+        string("AptosCoin"),
+        vec![0],
+        // args
+        vec![2, 32],
+        address(3),
+        vec![8],
+        300u64.to_le_bytes().to_vec(),
+        2u64.to_le_bytes().to_vec(),
+        3u64.to_le_bytes().to_vec(),
+        4u64.to_le_bytes().to_vec(),
+        vec![5],
+    ]
+    .concat();
+    let mut bytes = bcs::to_bytes(&raw)?;
+    assert_eq!(hex::encode(&bytes), hex::encode(&expected));
+    assert_eq!(bcs::from_bytes::<RawTransaction>(&bytes)?, raw);
+
+    // Другой вариант TransactionPayload
+    bytes[40] = 0;
+    assert!(bcs::from_bytes::<RawTransaction>(&bytes).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_sign_transaction() -> Result<()> {
+    use super::builtin_profile;
+
+    let alice = builtin_profile("alice")?.local_account()?;
+    let bob = builtin_profile("bob")?.account;
+    assert_eq!(alice.address(), builtin_profile("alice")?.account);
+    assert_eq!(alice.authentication_key(), alice.address());
+
+    let signed = alice.sign(RawTransaction {
+        sender: alice.address(),
+        sequence_number: 0,
+        payload: Variant(EntryFunction::transfer(bob, 10)?),
+        max_gas_amount: MAX_GAS_AMOUNT,
+        gas_unit_price: GAS_UNIT_PRICE,
+        expiration_timestamp_secs: 100,
+        chain_id: 4,
+    })?;
+    signed.verify(&alice.address())?;
+    assert!(signed.verify(&bob).is_err());
+
+    let decoded = bcs::from_bytes::<SignedTransaction>(&bcs::to_bytes(&signed)?)?;
+    assert_eq!(decoded, signed);
+    assert_eq!(decoded.hash()?, signed.hash()?);
+
+    let mut tampered = signed;
+    tampered.raw_txn.sequence_number = 1;
+    assert!(tampered.verify(&alice.address()).is_err());
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_transfer() -> Result<()> {
+    use super::{aptos_url, balance_at, client, AccountFactory};
+    use crate::{
+        deposit::wait_for_deltas,
+        engine_client::{new_client, MvEngine, RequestEngine},
+        engine_url,
+        jwt::get_jwt,
+        SLOTS,
+    };
+
+    let engine = new_client(&engine_url(), get_jwt().await)?;
+    let aptos = client();
+    let [sender, recipient] = AccountFactory::new("transfer").accounts()?;
+    let amount = 1_000;
+
+    debug!("Депозит покрывает перевод и максимальную комиссию");
+    let deposit = amount + MAX_GAS_AMOUNT * GAS_UNIT_PRICE;
+    let request = RequestEngine::builder()
+        .slot(|slot| slot.deposit(sender.address(), deposit))
+        .build(&engine, &SLOTS)
+        .await?;
+    engine.engine_apply_all(&request).await?;
+    wait_for_deltas(
+        &aptos_url(),
+        &[(sender.address(), 0)].into(),
+        &[(sender.address(), deposit.into())].into(),
+    )
+    .await?;
+
+    let transaction = aptos.transfer(&sender, recipient.address(), amount).await?;
+    debug!("{transaction:?}");
+    assert_eq!(transaction.sender, Some(sender.address()));
+    assert_eq!(
+        aptos
+            .transaction_by_version(transaction.version.context("Нет версии")?)
+            .await?
+            .hash,
+        transaction.hash
+    );
+
+    let fee = transaction.gas_used.context("Нет gas_used")? * GAS_UNIT_PRICE;
+    assert_eq!(
+        balance_at(&aptos_url(), &sender.address()).await?,
+        deposit - amount - fee
+    );
+    assert_eq!(
+        balance_at(&aptos_url(), &recipient.address()).await?,
+        amount
+    );
+    Ok(())
+}
@@ -0,0 +1,533 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use eyre::{ContextCompat, Result};
+use futures::future::try_join_all;
+use tracing::{debug, instrument};
+use tracing_test::traced_test;
+
+use crate::{
+    aptos::{balance_at, AccountAddress},
+    engine_client::{
+        ApplyAttributesResult, EventResult, MvEngine, RequestEngine, RequestEvent, RequestSlot,
+        TxDeposit,
+    },
+    wait::{Check, Wait},
+};
+
+/// Изменение баланса одного аккаунта.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BalanceDiff {
+    pub(crate) account: AccountAddress,
+    pub(crate) before: u128,
+    pub(crate) after: u128,
+    /// Сумма применённых депозитов на аккаунт.
+    pub(crate) expected: u128,
+}
+
+impl BalanceDiff {
+    pub(crate) fn actual(&self) -> i128 {
+        self.after as i128 - self.before as i128
+    }
+
+    pub(crate) fn is_ok(&self) -> bool {
+        self.actual() == self.expected as i128
+    }
+}
+
+/// Результат сверки балансов после отправки [`RequestEngine`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DepositReport {
+    pub(crate) rows: Vec<BalanceDiff>,
+}
+
+impl DepositReport {
+    pub(crate) fn is_ok(&self) -> bool {
+        self.rows.iter().all(BalanceDiff::is_ok)
+    }
+}
+
+impl fmt::Display for DepositReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<66} | {:>20} | {:>20} | {:>20} | {:>20}",
+            "account", "before", "after", "expected", "actual"
+        )?;
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{:<66} | {:>20} | {:>20} | {:>20} | {:>20}{}",
+                row.account.to_long_string(),
+                row.before,
+                row.after,
+                row.expected,
+                row.actual(),
+                if row.is_ok() { "" } else { "  <--" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Ожидаемые зачисления по аккаунтам. Учитываются только применённые нодой события.
+pub(crate) fn expected_deposits(
+    request: &RequestEngine,
+    result: Option<&ApplyAttributesResult>,
+) -> BTreeMap<AccountAddress, u128> {
+    let mut expected = BTreeMap::new();
+    for (slot_index, slot) in request.events.iter().enumerate() {
+        for (event_index, event) in slot.events.iter().enumerate() {
+            let applied = match result {
+                None => true,
+                Some(result) => matches!(
+                    result
+                        .slots
+                        .get(slot_index)
+                        .and_then(|slot| slot.events.get(event_index)),
+                    Some(EventResult::Applied)
+                ),
+            };
+            let RequestEvent::Deposit(deposit) = event else {
+                continue;
+            };
+            let amount = expected.entry(deposit.account).or_default();
+            if applied {
+                *amount += deposit.amount as u128;
+            }
+        }
+    }
+    expected
+}
+
+/// Текущие балансы аккаунтов.
+pub(crate) async fn balances(
+    aptos_url: &str,
+    accounts: impl IntoIterator<Item = AccountAddress>,
+) -> Result<BTreeMap<AccountAddress, u128>> {
+    let accounts = accounts.into_iter().collect::<Vec<_>>();
+    let balances = try_join_all(
+        accounts
+            .iter()
+            .map(|account| balance_at(aptos_url, account)),
+    )
+    .await?;
+    Ok(accounts
+        .into_iter()
+        .zip(balances)
+        .map(|(account, balance)| (account, u128::from(balance)))
+        .collect())
+}
+
+/// Ожидание, пока баланс каждого аккаунта из `expected` вырастет на ожидаемую
+/// сумму относительно `before`.
+pub(crate) async fn wait_for_deltas(
+    aptos_url: &str,
+    before: &BTreeMap<AccountAddress, u128>,
+    expected: &BTreeMap<AccountAddress, u128>,
+) -> Result<DepositReport> {
+    Wait::default()
+        .until("зачисление депозитов", || async {
+            let after = balances(aptos_url, expected.keys().copied()).await?;
+            let report = DepositReport {
+                rows: expected
+                    .iter()
+                    .map(|(account, expected)| BalanceDiff {
+                        account: *account,
+                        before: before.get(account).copied().unwrap_or_default(),
+                        after: after[account],
+                        expected: *expected,
+                    })
+                    .collect(),
+            };
+            Ok(if report.is_ok() {
+                Check::Ready(report)
+            } else {
+                Check::Pending(format!("\n{report}"))
+            })
+        })
+        .await
+}
+
+/// Отправка `request` и проверка, что баланс каждого аккаунта из запроса
+/// вырос ровно на сумму его депозитов.
+#[instrument(level = "debug", skip(client, request))]
+pub(crate) async fn verify_deposits<C>(
+    client: &C,
+    aptos_url: &str,
+    request: &RequestEngine,
+) -> Result<(ApplyAttributesResult, DepositReport)>
+where
+    C: MvEngine + Sync,
+{
+    let before = balances(aptos_url, expected_deposits(request, None).into_keys()).await?;
+    debug!("Балансы до отправки: {before:?}");
+
+    let result = client.engine_applyattributes_v1(request).await?;
+    result.ensure_matches(request)?;
+    let expected = expected_deposits(request, Some(&result));
+
+    let report = wait_for_deltas(aptos_url, &before, &expected).await?;
+    Ok((result, report))
+}
+
+#[test]
+fn test_deposit_report() {
+    let report = DepositReport {
+        rows: vec![
+            BalanceDiff {
+                account: AccountAddress::ONE,
+                before: 10,
+                after: 15,
+                expected: 5,
+            },
+            BalanceDiff {
+                account: "0x2".parse().unwrap(),
+                before: 10,
+                after: 10,
+                expected: 3,
+            },
+        ],
+    };
+    assert!(!report.is_ok());
+
+    let table = report.to_string();
+    assert_eq!(table.lines().count(), 3);
+    assert!(table.lines().nth(2).unwrap().ends_with("<--"));
+    assert!(!table.lines().nth(1).unwrap().ends_with("<--"));
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_verify_deposits_mock() -> Result<()> {
+    use crate::{aptos::builtin_profile, engine_client::new_client, mock::MockNode};
+
+    let node = MockNode::start().await?;
+    let client = new_client(&node.engine_url, node.jwt)?;
+    let [alice, bob] = [
+        builtin_profile("alice")?.account,
+        builtin_profile("bob")?.account,
+    ];
+    let deposit = |account, amount| RequestEvent::Deposit(TxDeposit { account, amount });
+    let request = RequestEngine {
+        parent_payload: 0,
+        max_payload_size: 1001,
+        events: vec![RequestSlot {
+            slot: 1,
+            events: vec![deposit(alice, 1), deposit(bob, 2), deposit(alice, 3)],
+        }],
+    };
+
+    let (_, report) = verify_deposits(&client, &node.aptos_url, &request).await?;
+    debug!("\n{report}");
+    assert_eq!(report.rows.len(), 2, "Несколько депозитов на один аккаунт");
+    assert!(report.rows.iter().any(|row| row.expected == 4));
+
+    Ok(())
+}
+
+/// Повторная отправка уже применённого слота не должна зачислять депозиты второй раз.
+/// Нода может отклонить такой запрос или принять его без повторного применения слота.
+#[traced_test]
+#[tokio::test]
+async fn test_deposit_resubmit() -> Result<()> {
+    use std::time::Duration;
+
+    use eyre::ensure;
+
+    use crate::{
+        aptos::aptos_url,
+        engine_client::{new_client, rpc_error, PayloadChain},
+        engine_url,
+        jwt::get_jwt,
+        SLOTS,
+    };
+
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let aptos_url = aptos_url();
+    let mut chain = PayloadChain::from_node(&client).await?;
+    let [alice, bob] = [
+        AccountAddress::new(rand::random()),
+        AccountAddress::new(rand::random()),
+    ];
+
+    let slot = SLOTS.next(&client).await?;
+    let request = RequestEngine::builder()
+        .parent_payload(chain.head())
+        .slot_at(slot, |events| events.deposit(alice, 10).deposit(alice, 5))
+        .build(&client, &SLOTS)
+        .await?;
+    let (result, report) = verify_deposits(&client, &aptos_url, &request).await?;
+    debug!("\n{report}");
+    result.ensure_all_applied()?;
+    chain.record(&result);
+    let mut credited = balances(&aptos_url, [alice, bob]).await?;
+    // Время, за которое повторное зачисление успело бы отразиться в балансе
+    let settle = Wait::timeout(Duration::from_secs(2));
+
+    let resubmits = [
+        ("тот же запрос от нового head", {
+            let mut request = request.clone();
+            request.parent_payload = chain.head();
+            request
+        }),
+        ("тот же запрос от исходного parent", request.clone()),
+        (
+            "другие события в том же слоте",
+            RequestEngine::builder()
+                .parent_payload(chain.head())
+                .slot_at(slot, |events| events.deposit(alice, 7).deposit(bob, 7))
+                .build(&client, &SLOTS)
+                .await?,
+        ),
+        (
+            "применённый слот вместе с новым",
+            RequestEngine::builder()
+                .parent_payload(chain.head())
+                .slot_at(slot, |events| events.deposit(bob, 1))
+                .slot(|events| events.deposit(bob, 1))
+                .build(&client, &SLOTS)
+                .await?,
+        ),
+    ];
+    for (name, resubmit) in resubmits {
+        debug!("{name}: {resubmit:?}");
+        match client.engine_applyattributes_v1(&resubmit).await {
+            Err(err) => {
+                let error = rpc_error(&err)
+                    .with_context(|| format!("{name}: запрос не дошёл до ноды: {err:#}"))?;
+                debug!("{name}: отклонён: {error:?}");
+            }
+            Ok(result) => {
+                debug!("{name}: принят: {result:?}");
+                result.ensure_matches(&resubmit)?;
+                chain.record(&result);
+                // Депозиты новых слотов зачисляются как обычно
+                for (request_slot, slot_result) in resubmit.events.iter().zip(&result.slots) {
+                    if request_slot.slot == slot {
+                        continue;
+                    }
+                    for (event, event_result) in request_slot.events.iter().zip(&slot_result.events)
+                    {
+                        if let (RequestEvent::Deposit(deposit), EventResult::Applied) =
+                            (event, event_result)
+                        {
+                            *credited.entry(deposit.account).or_default() +=
+                                u128::from(deposit.amount);
+                        }
+                    }
+                }
+            }
+        }
+
+        let reached = || async {
+            let actual = balances(&aptos_url, [alice, bob]).await?;
+            Ok(if actual == credited {
+                Check::Ready(())
+            } else {
+                Check::Pending(format!("{actual:?}, ожидается {credited:?}"))
+            })
+        };
+        Wait::default()
+            .until(&format!("{name}: зачисление новых слотов"), reached)
+            .await?;
+        settle
+            .during(
+                &format!("{name}: нет повторного зачисления"),
+                || async {
+                    let actual = balances(&aptos_url, [alice, bob]).await?;
+                    ensure!(actual == credited, "{actual:?}, ожидается {credited:?}");
+                    Ok(())
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Одновременная отправка одного слота: депозит слота зачисляется ровно один раз.
+/// Как и в [`test_deposit_resubmit`], нода может отклонить повторы или принять их
+/// без повторного применения слота, поэтому проверяется зачисление, а не ответы.
+#[traced_test]
+#[tokio::test]
+async fn test_deposit_resubmit_concurrent() -> Result<()> {
+    use std::time::Duration;
+
+    use eyre::ensure;
+    use futures::future::join_all;
+
+    use crate::{
+        aptos::aptos_url,
+        engine_client::{new_client, PayloadChain},
+        engine_url,
+        jwt::get_jwt,
+        SLOTS,
+    };
+
+    const SUBMITS: u64 = 8;
+
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let aptos_url = aptos_url();
+    let head = PayloadChain::from_node(&client).await?.head();
+    let account = AccountAddress::new(rand::random());
+    // Время, за которое повторное зачисление успело бы отразиться в балансе
+    let settle = Wait::timeout(Duration::from_secs(2));
+
+    for conflicting in [false, true] {
+        let slot = SLOTS.next(&client).await?;
+        // Одинаковые запросы или с разной суммой депозита
+        let requests = (1..=SUBMITS)
+            .map(|index| RequestEngine {
+                parent_payload: head,
+                max_payload_size: 1001,
+                events: vec![RequestSlot {
+                    slot,
+                    events: vec![RequestEvent::Deposit(TxDeposit {
+                        account,
+                        amount: if conflicting { index } else { 1 },
+                    })],
+                }],
+            })
+            .collect::<Vec<_>>();
+
+        let before = balances(&aptos_url, [account]).await?[&account];
+        let results = join_all(
+            requests
+                .iter()
+                .map(|request| client.engine_applyattributes_v1(request)),
+        )
+        .await;
+        // Суммы принятых запросов: зачислиться должна одна из них
+        let accepted = requests
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_ok())
+            .map(|(request, _)| expected_deposits(request, None)[&account])
+            .collect::<BTreeSet<_>>();
+        debug!("conflicting: {conflicting}, принятые суммы: {accepted:?}");
+        ensure!(
+            !accepted.is_empty(),
+            "conflicting: {conflicting}. Ни один запрос слота {slot} не принят"
+        );
+
+        let credited = || async {
+            Ok::<_, eyre::Report>(balances(&aptos_url, [account]).await?[&account] - before)
+        };
+        let delta = Wait::default()
+            .until(
+                &format!("conflicting: {conflicting}: зачисление"),
+                || async {
+                    let delta = credited().await?;
+                    Ok(if delta > 0 {
+                        Check::Ready(delta)
+                    } else {
+                        Check::Pending("баланс не изменился".into())
+                    })
+                },
+            )
+            .await?;
+        ensure!(
+            accepted.contains(&delta),
+            "conflicting: {conflicting}. Слот {slot} зачислил {delta}, \
+             а не сумму одного из принятых запросов {accepted:?}"
+        );
+        settle
+            .during(
+                &format!("conflicting: {conflicting}: нет повторного зачисления"),
+                || async {
+                    let actual = credited().await?;
+                    ensure!(actual == delta, "Зачислено {actual}, ожидается {delta}");
+                    Ok(())
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Граничные суммы депозита: ноль, `u64::MAX` и переполнение баланса аккаунта.
+#[traced_test]
+#[tokio::test]
+async fn test_deposit_amount_edges() -> Result<()> {
+    use jsonrpsee::{core::client::ClientT, rpc_params, types::error::INVALID_PARAMS_CODE};
+    use serde_json::value::RawValue;
+
+    use crate::{
+        aptos::aptos_url,
+        engine_client::{new_client, rpc_error, PayloadChain},
+        engine_url,
+        jwt::get_jwt,
+        wait::wait_for_balance,
+        SLOTS,
+    };
+
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let aptos_url = aptos_url();
+    let mut chain = PayloadChain::from_node(&client).await?;
+    let [zero, full, accumulated] = [(); 3].map(|_| AccountAddress::new(rand::random()));
+
+    debug!("Нулевой депозит и u64::MAX на новые аккаунты");
+    let request = RequestEngine::builder()
+        .slot(|events| events.deposit(zero, 0).deposit(full, u64::MAX))
+        .slot(|events| events.deposit(accumulated, u64::MAX - 1))
+        .build(&client, &SLOTS)
+        .await?;
+    chain.apply(&client, request).await?.ensure_all_applied()?;
+    let wait = Wait::default();
+    wait_for_balance(&aptos_url, &full, u64::MAX, &wait).await?;
+    wait_for_balance(&aptos_url, &zero, 0, &wait).await?;
+
+    debug!("Накопление до u64::MAX и переполнение");
+    let request = RequestEngine::builder()
+        .slot(|events| {
+            events
+                .deposit(accumulated, 1)
+                .deposit(accumulated, 1)
+                .deposit(full, 1)
+                .deposit(zero, 1)
+        })
+        .build(&client, &SLOTS)
+        .await?;
+    let slot = request.events[0].slot;
+    let result = chain.apply(&client, request).await?;
+    debug!("response: {result:#?}");
+    assert_eq!(result.applied_count(), 2);
+    result.ensure_rejected_with(slot, 1, EventResult::BALANCE_OVERFLOW)?;
+    result.ensure_rejected_with(slot, 2, EventResult::BALANCE_OVERFLOW)?;
+    wait_for_balance(&aptos_url, &accumulated, u64::MAX, &wait).await?;
+    wait_for_balance(&aptos_url, &zero, 1, &wait).await?;
+    wait_for_balance(&aptos_url, &full, u64::MAX, &wait).await?;
+
+    debug!("Сумма больше u64::MAX в запросе");
+    // serde_json::Value не вмещает такое число, поэтому запрос собирается строкой
+    let request = RawValue::from_string(format!(
+        r#"{{
+            "parent_payload": {},
+            "max_payload_size": 1001,
+            "events": [{{
+                "slot": {},
+                "events": [{{ "Deposit": {{ "account": "{zero}", "amount": {} }} }}]
+            }}]
+        }}"#,
+        chain.head(),
+        SLOTS.next(&client).await?,
+        u128::from(u64::MAX) + 1
+    ))?;
+    let err = client
+        .request::<serde_json::Value, _>("engine_applyAttributes_v1", rpc_params![request])
+        .await
+        .err()
+        .map(eyre::Report::from)
+        .context("Сумма больше u64::MAX должна отклоняться")?;
+    assert_eq!(
+        rpc_error(&err).map(|err| err.code()),
+        Some(INVALID_PARAMS_CODE),
+        "{err:#}"
+    );
+
+    Ok(())
+}
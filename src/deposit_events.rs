@@ -0,0 +1,413 @@
+use std::{collections::BTreeSet, fmt};
+
+use eyre::{ensure, Context, ContextCompat, Result};
+use serde::Deserialize;
+use tracing::{debug, instrument};
+use tracing_test::traced_test;
+
+use crate::{
+    aptos::{from_str, AccountAddress, AptosClient, Event},
+    engine_client::{ApplyAttributesResult, EventResult, MvEngine, RequestEngine, RequestEvent},
+    wait::{wait_for_ledger_version, Check, Wait},
+    Slot,
+};
+
+// Схема событий депозитов - предположение: нода пока не документирует, какое событие
+// она выпускает на `Deposit`. Стандартные `0x1::coin::DepositEvent` и
+// `0x1::fungible_asset::Deposit` не содержат слот L1, поэтому не позволяют сопоставить
+// событие с депозитом запроса. Константы ниже и `DepositEvent` нужно поменять,
+// когда схема станет известна.
+
+/// Ресурс `0x1` с хэндлом событий депозитов L2. Предполагаемая схема.
+pub(crate) const DEPOSIT_EVENTS: &str = "0x1::l2_bridge::DepositEvents";
+/// Поле [`DEPOSIT_EVENTS`] с хэндлом событий.
+pub(crate) const DEPOSIT_EVENTS_FIELD: &str = "deposit_events";
+/// Тип события, которое нода выпускает на каждый применённый депозит. Предполагаемая схема.
+pub(crate) const DEPOSIT_EVENT: &str = "0x1::l2_bridge::DepositEvent";
+/// Событий на страницу при чтении хэндла.
+const PAGE_SIZE: u16 = 100;
+
+/// Применённый нодой депозит, для которого ожидается событие.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct ExpectedDeposit {
+    pub(crate) slot: Slot,
+    pub(crate) account: AccountAddress,
+    pub(crate) amount: u64,
+}
+
+/// Событие депозита из Aptos REST API. Поля - предполагаемая схема [`DEPOSIT_EVENT`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct DepositEvent {
+    #[serde(skip)]
+    pub(crate) sequence_number: u64,
+    pub(crate) account: AccountAddress,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) amount: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub(crate) slot: Slot,
+}
+
+impl DepositEvent {
+    pub(crate) fn parse(event: &Event) -> Result<Self> {
+        ensure!(
+            event.event_type == DEPOSIT_EVENT,
+            "Событие {} типа {} вместо {DEPOSIT_EVENT}",
+            event.sequence_number,
+            event.event_type
+        );
+        let data = serde_json::from_value::<Self>(event.data.clone()).with_context(|| {
+            format!(
+                "Данные события {} не соответствуют схеме: {}",
+                event.sequence_number, event.data
+            )
+        })?;
+        Ok(Self {
+            sequence_number: event.sequence_number,
+            ..data
+        })
+    }
+
+    fn matches(&self, expected: &ExpectedDeposit) -> bool {
+        self.slot == expected.slot
+            && self.account == expected.account
+            && self.amount == expected.amount
+    }
+}
+
+impl fmt::Display for ExpectedDeposit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slot {} {} {}",
+            self.slot,
+            self.account.to_long_string(),
+            self.amount
+        )
+    }
+}
+
+impl fmt::Display for DepositEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "slot {} {} {} (#{})",
+            self.slot,
+            self.account.to_long_string(),
+            self.amount,
+            self.sequence_number
+        )
+    }
+}
+
+/// Сопоставление событий депозитов с депозитами запроса, один к одному.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DepositEventReport {
+    pub(crate) matched: Vec<(ExpectedDeposit, DepositEvent)>,
+    /// Событие в том же слоте, но с другим аккаунтом или суммой.
+    pub(crate) mismatched: Vec<(ExpectedDeposit, DepositEvent)>,
+    pub(crate) missing: Vec<ExpectedDeposit>,
+    /// События в слотах запроса, которым не нашлось депозита.
+    pub(crate) extra: Vec<DepositEvent>,
+}
+
+impl DepositEventReport {
+    /// События из слотов, которых нет в `request`, не учитываются.
+    pub(crate) fn new(
+        request: &RequestEngine,
+        result: Option<&ApplyAttributesResult>,
+        events: Vec<DepositEvent>,
+    ) -> Self {
+        let slots = request
+            .events
+            .iter()
+            .map(|slot| slot.slot)
+            .collect::<BTreeSet<_>>();
+        let mut events = events
+            .into_iter()
+            .filter(|event| slots.contains(&event.slot))
+            .collect::<Vec<_>>();
+
+        let mut report = Self::default();
+        let mut unmatched = Vec::new();
+        for expected in expected_deposit_events(request, result) {
+            match events.iter().position(|event| event.matches(&expected)) {
+                Some(index) => report.matched.push((expected, events.remove(index))),
+                None => unmatched.push(expected),
+            }
+        }
+        // Без точного совпадения - событие того же слота с тем же аккаунтом или суммой
+        for expected in unmatched {
+            match events.iter().position(|event| {
+                event.slot == expected.slot
+                    && (event.account == expected.account || event.amount == expected.amount)
+            }) {
+                Some(index) => report.mismatched.push((expected, events.remove(index))),
+                None => report.missing.push(expected),
+            }
+        }
+        report.extra = events;
+        report
+    }
+
+    pub(crate) fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl fmt::Display for DepositEventReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "matched: {}, mismatched: {}, missing: {}, extra: {}",
+            self.matched.len(),
+            self.mismatched.len(),
+            self.missing.len(),
+            self.extra.len()
+        )?;
+        for (expected, event) in &self.mismatched {
+            writeln!(f, "mismatched: {expected} <-> {event}")?;
+        }
+        for expected in &self.missing {
+            writeln!(f, "missing:    {expected}")?;
+        }
+        for event in &self.extra {
+            writeln!(f, "extra:      {event}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Депозиты запроса в порядке отправки. Учитываются только применённые нодой события.
+pub(crate) fn expected_deposit_events(
+    request: &RequestEngine,
+    result: Option<&ApplyAttributesResult>,
+) -> Vec<ExpectedDeposit> {
+    let mut expected = Vec::new();
+    for (slot_index, slot) in request.events.iter().enumerate() {
+        for (event_index, event) in slot.events.iter().enumerate() {
+            let RequestEvent::Deposit(deposit) = event else {
+                continue;
+            };
+            let applied = match result {
+                None => true,
+                Some(result) => matches!(
+                    result
+                        .slots
+                        .get(slot_index)
+                        .and_then(|slot| slot.events.get(event_index)),
+                    Some(EventResult::Applied)
+                ),
+            };
+            if applied {
+                expected.push(ExpectedDeposit {
+                    slot: slot.slot,
+                    account: deposit.account,
+                    amount: deposit.amount,
+                });
+            }
+        }
+    }
+    expected
+}
+
+/// Число событий в хэндле депозитов. 0, если ресурса ещё нет.
+pub(crate) async fn deposit_event_counter(aptos: &AptosClient) -> Result<u64> {
+    let Some(resource) = aptos.resource(&AccountAddress::ONE, DEPOSIT_EVENTS).await? else {
+        return Ok(0);
+    };
+    let counter = &resource.data[DEPOSIT_EVENTS_FIELD]["counter"];
+    counter
+        .as_str()
+        .and_then(|counter| counter.parse().ok())
+        .with_context(|| format!("Не валидный counter в {DEPOSIT_EVENTS}: {counter}"))
+}
+
+/// События депозитов начиная с `sequence_number` `start`.
+#[instrument(level = "debug", skip(aptos))]
+pub(crate) async fn deposit_events(aptos: &AptosClient, start: u64) -> Result<Vec<DepositEvent>> {
+    let counter = deposit_event_counter(aptos).await?;
+    let mut events = Vec::new();
+    let mut next = start;
+    while next < counter {
+        let page = aptos
+            .events_by_event_handle(
+                &AccountAddress::ONE,
+                DEPOSIT_EVENTS,
+                DEPOSIT_EVENTS_FIELD,
+                Some(next),
+                Some(PAGE_SIZE),
+            )
+            .await?;
+        ensure!(
+            !page.is_empty(),
+            "Нет событий депозитов начиная с {next}, counter: {counter}"
+        );
+        next += page.len() as u64;
+        for event in &page {
+            events.push(DepositEvent::parse(event)?);
+        }
+    }
+    debug!("{} событий", events.len());
+    Ok(events)
+}
+
+/// Отправка `request` и сверка событий депозитов с депозитами запроса.
+/// Ждёт, пока каждому применённому депозиту не найдётся событие и Aptos REST API
+/// не дойдёт до версии леджера после payload. Отчёт строится по событиям,
+/// прочитанным на этой версии, чтобы в него попали и лишние события.
+#[instrument(level = "debug", skip(client, aptos, request))]
+pub(crate) async fn verify_deposit_events<C>(
+    client: &C,
+    aptos: &AptosClient,
+    request: &RequestEngine,
+) -> Result<(ApplyAttributesResult, DepositEventReport)>
+where
+    C: MvEngine + Sync,
+{
+    let start = deposit_event_counter(aptos).await?;
+    debug!("Событий депозитов до отправки: {start}");
+
+    let result = client.engine_applyattributes_v1(request).await?;
+    result.ensure_matches(request)?;
+    // Payload уже применён, версия головы не меньше версии payload
+    let version = client.engine_l2info_v1().await?.ledger_version;
+
+    let wait = Wait::default();
+    wait.until("события депозитов", || async {
+        let events = deposit_events(aptos, start).await?;
+        let report = DepositEventReport::new(request, Some(&result), events);
+        Ok(if report.missing.is_empty() {
+            Check::Ready(())
+        } else {
+            Check::Pending(format!("\n{report}"))
+        })
+    })
+    .await?;
+
+    // События после последнего ожидаемого ещё могли не дойти до REST API
+    wait_for_ledger_version(aptos, version, &wait).await?;
+    let events = deposit_events(aptos, start).await?;
+    let report = DepositEventReport::new(request, Some(&result), events);
+    Ok((result, report))
+}
+
+#[test]
+fn test_deposit_event_report() {
+    use crate::engine_client::{RequestSlot, SlotResult, TxDeposit};
+
+    let [alice, bob, eve] = [1, 2, 3].map(|byte| AccountAddress::new([byte; 32]));
+    let deposit = |account, amount| RequestEvent::Deposit(TxDeposit { account, amount });
+    let request = RequestEngine {
+        parent_payload: 0,
+        max_payload_size: 1001,
+        events: vec![
+            RequestSlot {
+                slot: 10,
+                events: vec![deposit(alice, 5), deposit(alice, 5), deposit(bob, 7)],
+            },
+            RequestSlot {
+                slot: 11,
+                events: vec![deposit(eve, 1), deposit(bob, u64::MAX)],
+            },
+        ],
+    };
+    let result = ApplyAttributesResult {
+        payload_id: 1,
+        slots: vec![
+            SlotResult {
+                slot: 10,
+                events: vec![EventResult::Applied; 3],
+            },
+            SlotResult {
+                slot: 11,
+                events: vec![
+                    EventResult::Applied,
+                    EventResult::Rejected {
+                        code: EventResult::BALANCE_OVERFLOW,
+                        message: String::new(),
+                    },
+                ],
+            },
+        ],
+    };
+    let event = |sequence_number, slot, account, amount| DepositEvent {
+        sequence_number,
+        account,
+        amount,
+        slot,
+    };
+    let events = vec![
+        event(0, 10, alice, 5),
+        event(1, 9, eve, 1),
+        event(2, 10, bob, 8),
+        event(3, 10, alice, 5),
+        event(4, 11, bob, u64::MAX),
+    ];
+
+    let report = DepositEventReport::new(&request, Some(&result), events);
+    assert!(!report.is_ok());
+    let sequence_numbers = |events: Vec<&DepositEvent>| {
+        events
+            .into_iter()
+            .map(|event| event.sequence_number)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        sequence_numbers(report.matched.iter().map(|(_, event)| event).collect()),
+        [0, 3],
+        "Одинаковые депозиты сопоставляются с разными событиями"
+    );
+    assert_eq!(
+        sequence_numbers(report.mismatched.iter().map(|(_, event)| event).collect()),
+        [2]
+    );
+    assert_eq!(report.mismatched[0].0.amount, 7);
+    assert_eq!(
+        report.missing,
+        [ExpectedDeposit {
+            slot: 11,
+            account: eve,
+            amount: 1,
+        }]
+    );
+    assert_eq!(
+        sequence_numbers(report.extra.iter().collect()),
+        [4],
+        "Событие отклонённого депозита, событие слота 9 не учитывается"
+    );
+    assert_eq!(report.to_string().lines().count(), 4);
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_verify_deposit_events() -> Result<()> {
+    use crate::{
+        aptos::{client, AccountFactory},
+        engine_client::new_client,
+        engine_url,
+        jwt::get_jwt,
+        SLOTS,
+    };
+
+    let engine = new_client(&engine_url(), get_jwt().await)?;
+    let aptos = client();
+    let [alice, bob] = AccountFactory::new("deposit_events")
+        .accounts()?
+        .map(|account| account.address());
+
+    let request = RequestEngine::builder()
+        .slot(|slot| slot.deposit(alice, 5).deposit(alice, 5).deposit(bob, 7))
+        // Переполнение баланса: депозит отклоняется, события быть не должно
+        .slot(|slot| slot.deposit(bob, u64::MAX).deposit(alice, 0))
+        .build(&engine, &SLOTS)
+        .await?;
+    let (result, report) = verify_deposit_events(&engine, &aptos, &request).await?;
+    debug!("\n{report}");
+    assert!(matches!(
+        result.slots[1].events[0],
+        EventResult::Rejected { code, .. } if code == EventResult::BALANCE_OVERFLOW
+    ));
+    assert!(report.is_ok(), "{report}");
+    assert_eq!(report.matched.len(), 4);
+    Ok(())
+}
@@ -0,0 +1,459 @@
+use eyre::{bail, ensure, ContextCompat, Result};
+use serde::{Deserialize, Serialize};
+
+use super::PayloadId;
+use crate::{aptos::AccountAddress, Slot};
+
+/// Атрибуты для `engine_applyAttributes_v1`.
+///
+/// `max_payload_size` ограничивает размер создаваемого payload. В каких единицах,
+/// API ноды не описывает. Предположение: это количество транзакций L2, не больше
+/// одной на событие. Запрос, который не помещается в лимит, нода отклоняет целиком,
+/// поэтому большие запросы нужно делить через [`RequestEngine::split`].
+/// Предположение проверяет `test_max_payload_size` на реальной ноде.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct RequestEngine {
+    pub(crate) parent_payload: PayloadId,
+    pub(crate) max_payload_size: u64,
+    pub(crate) events: Vec<RequestSlot>,
+}
+
+// Коды ошибок и лимит ниже API ноды не описывает, значения взяты из mock-ноды.
+impl RequestEngine {
+    /// Код ошибки JSON-RPC: `parent_payload` не найден.
+    /// Предположение, проверяет `invalid_attributes::test_invalid_attributes`.
+    pub(crate) const UNKNOWN_PARENT_CODE: i32 = -38001;
+    /// Код ошибки JSON-RPC: невалидные слоты или `max_payload_size`.
+    /// Предположение, проверяют `invalid_attributes::test_invalid_attributes`
+    /// и `test_max_payload_size`.
+    pub(crate) const INVALID_ATTRIBUTES_CODE: i32 = -38003;
+    /// Наибольший `max_payload_size`, который принимает нода. Предположение,
+    /// `invalid_attributes::test_invalid_attributes` проверяет отказ на `limit + 1`.
+    pub(crate) const MAX_PAYLOAD_SIZE_LIMIT: u64 = 1 << 16;
+
+    /// Общее количество событий во всех слотах.
+    pub(crate) fn events_count(&self) -> usize {
+        self.events.iter().map(|slot| slot.events.len()).sum()
+    }
+
+    /// Оценка размера payload сверху в предположении, что лимит считает транзакции L2:
+    /// каждое событие, даже отклонённое нодой, считается одной транзакцией.
+    pub(crate) fn payload_size(&self) -> u64 {
+        self.events_count() as u64
+    }
+
+    /// Проверка инвариантов запроса: слоты строго возрастают, `max_payload_size`
+    /// в допустимых пределах и payload в него помещается.
+    pub(crate) fn validate(&self) -> Result<()> {
+        ensure!(self.max_payload_size > 0, "max_payload_size равен 0");
+        ensure!(
+            self.max_payload_size <= Self::MAX_PAYLOAD_SIZE_LIMIT,
+            "max_payload_size {} больше {}",
+            self.max_payload_size,
+            Self::MAX_PAYLOAD_SIZE_LIMIT
+        );
+        for pair in self.events.windows(2) {
+            ensure!(
+                pair[0].slot < pair[1].slot,
+                "Слоты должны строго возрастать: {} -> {}",
+                pair[0].slot,
+                pair[1].slot
+            );
+        }
+        ensure!(
+            self.payload_size() <= self.max_payload_size,
+            "Размер payload {} больше max_payload_size {}",
+            self.payload_size(),
+            self.max_payload_size
+        );
+        Ok(())
+    }
+
+    /// Деление на запросы, каждый из которых помещается в `max_payload_size`.
+    ///
+    /// Слот целиком попадает в один запрос: повторно отправить слот нельзя.
+    /// `parent_payload` сохраняется только у первого запроса, остальные должны
+    /// отправляться по цепочке через [`super::PayloadChain::apply_split`].
+    pub(crate) fn split(&self) -> Result<Vec<RequestEngine>> {
+        let mut parts = Vec::new();
+        let mut current = RequestEngine {
+            parent_payload: self.parent_payload,
+            max_payload_size: self.max_payload_size,
+            events: Vec::new(),
+        };
+        for slot in &self.events {
+            let size = slot.events.len() as u64;
+            ensure!(
+                size <= self.max_payload_size,
+                "Слот {} содержит {size} событий и не помещается в max_payload_size {}",
+                slot.slot,
+                self.max_payload_size
+            );
+            if current.payload_size() + size > self.max_payload_size {
+                let next = RequestEngine {
+                    events: Vec::new(),
+                    ..current.clone()
+                };
+                parts.push(std::mem::replace(&mut current, next));
+            }
+            current.events.push(slot.clone());
+        }
+        if !current.events.is_empty() || parts.is_empty() {
+            parts.push(current);
+        }
+        Ok(parts)
+    }
+}
+
+/// События одного слота L1.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct RequestSlot {
+    pub(crate) slot: Slot,
+    pub(crate) events: Vec<RequestEvent>,
+}
+
+/// Событие L1, которое нода должна отразить в L2.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) enum RequestEvent {
+    Deposit(TxDeposit),
+    WithdrawalAck(TxWithdrawalAck),
+    ForcedTransaction(TxForced),
+    Message(TxMessage),
+    RegisterAsset(TxRegisterAsset),
+}
+
+/// Пополнение аккаунта L2.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct TxDeposit {
+    pub(crate) account: AccountAddress,
+    pub(crate) amount: u64,
+}
+
+/// Подтверждение, что вывод средств из L2 исполнен на L1.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct TxWithdrawalAck {
+    /// Идентификатор вывода, выданный L2.
+    pub(crate) withdrawal_id: u64,
+    pub(crate) account: AccountAddress,
+    pub(crate) amount: u64,
+}
+
+/// Транзакция L2, отправленная через L1 в обход секвенсора.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct TxForced {
+    pub(crate) sender: AccountAddress,
+    /// BCS payload транзакции в hex с префиксом `0x`.
+    pub(crate) payload: String,
+}
+
+/// Произвольное сообщение из L1 в L2.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct TxMessage {
+    /// Адрес отправителя в L1.
+    pub(crate) sender: String,
+    /// Аккаунт получателя в L2.
+    pub(crate) target: AccountAddress,
+    /// Уникален для каждого отправителя.
+    pub(crate) nonce: u64,
+    /// Данные сообщения в hex с префиксом `0x`.
+    pub(crate) data: String,
+}
+
+/// Регистрация в L2 токена из L1.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct TxRegisterAsset {
+    /// Адрес токена в L1.
+    pub(crate) l1_token: String,
+    pub(crate) symbol: String,
+    pub(crate) decimals: u8,
+}
+
+/// Ответ `engine_applyAttributes_v1`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct ApplyAttributesResult {
+    /// Идентификатор созданного payload.
+    pub(crate) payload_id: PayloadId,
+    /// Результаты по каждому слоту в порядке запроса.
+    pub(crate) slots: Vec<SlotResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct SlotResult {
+    pub(crate) slot: Slot,
+    /// Результаты по каждому событию слота в порядке запроса.
+    pub(crate) events: Vec<EventResult>,
+}
+
+/// Результат применения одного события.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "status")]
+pub(crate) enum EventResult {
+    Applied,
+    Rejected { code: i64, message: String },
+}
+
+// Коды отклонения событий API ноды не описывает, значения взяты из mock-ноды.
+impl EventResult {
+    /// Код ошибки: невалидный адрес аккаунта. Предположение, проверяют
+    /// `test_deposit_address_forms` и `scenarios/invalid_account.yaml`.
+    pub(crate) const INVALID_ACCOUNT: i64 = 1;
+    /// Код ошибки: событие с таким идентификатором уже было применено.
+    /// Предположение, на реальной ноде пока не проверяется.
+    pub(crate) const DUPLICATE: i64 = 2;
+    /// Код ошибки: невалидные данные события. Предположение,
+    /// проверяет `scenarios/rejected_event.yaml`.
+    pub(crate) const INVALID_PAYLOAD: i64 = 3;
+    /// Код ошибки: баланс аккаунта превысил бы `u64::MAX`. Предположение,
+    /// проверяют `test_deposit_amount_edges` и `test_verify_deposit_events`.
+    pub(crate) const BALANCE_OVERFLOW: i64 = 4;
+}
+
+impl ApplyAttributesResult {
+    /// Все отклонённые события: (слот, индекс события в слоте, код, сообщение).
+    pub(crate) fn rejected(&self) -> impl Iterator<Item = (Slot, usize, i64, &str)> {
+        self.slots.iter().flat_map(|slot| {
+            slot.events
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, event)| match event {
+                    EventResult::Applied => None,
+                    EventResult::Rejected { code, message } => {
+                        Some((slot.slot, index, *code, message.as_str()))
+                    }
+                })
+        })
+    }
+
+    /// Количество применённых событий.
+    pub(crate) fn applied_count(&self) -> usize {
+        self.slots
+            .iter()
+            .flat_map(|slot| &slot.events)
+            .filter(|event| matches!(event, EventResult::Applied))
+            .count()
+    }
+
+    /// Проверка, что все события были применены.
+    pub(crate) fn ensure_all_applied(&self) -> Result<()> {
+        let rejected = self
+            .rejected()
+            .map(|(slot, index, code, message)| {
+                format!("слот {slot}, событие {index}: [{code}] {message}")
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            rejected.is_empty(),
+            "Нода отклонила события:\n{}",
+            rejected.join("\n")
+        );
+        Ok(())
+    }
+
+    /// Проверка, что событие `index` в слоте `slot` было отклонено с кодом `code`.
+    pub(crate) fn ensure_rejected_with(&self, slot: Slot, index: usize, code: i64) -> Result<()> {
+        let event = self
+            .slots
+            .iter()
+            .find(|result| result.slot == slot)
+            .and_then(|result| result.events.get(index))
+            .with_context(|| format!("В ответе нет события {index} слота {slot}"))?;
+        match event {
+            EventResult::Rejected {
+                code: actual,
+                message,
+            } => ensure!(
+                *actual == code,
+                "Событие {index} слота {slot} отклонено с кодом {actual} вместо {code}: {message}"
+            ),
+            EventResult::Applied => {
+                bail!("Событие {index} слота {slot} применено, ожидалась ошибка с кодом {code}")
+            }
+        }
+        Ok(())
+    }
+
+    /// Проверка, что ответ содержит результаты ровно по тем слотам и событиям,
+    /// которые были отправлены.
+    pub(crate) fn ensure_matches(&self, request: &RequestEngine) -> Result<()> {
+        let expected = request
+            .events
+            .iter()
+            .map(|slot| (slot.slot, slot.events.len()))
+            .collect::<Vec<_>>();
+        let actual = self
+            .slots
+            .iter()
+            .map(|slot| (slot.slot, slot.events.len()))
+            .collect::<Vec<_>>();
+        ensure!(
+            expected == actual,
+            "Ответ не соответствует запросу. (слот, количество событий) отправлено: {expected:?}, получено: {actual:?}"
+        );
+        Ok(())
+    }
+}
+
+fn example_result() -> ApplyAttributesResult {
+    serde_json::from_value(serde_json::json!({
+        "payload_id": 5,
+        "slots": [
+            {
+                "slot": 10,
+                "events": [
+                    { "status": "Applied" },
+                    { "status": "Rejected", "code": 3, "message": "invalid account" }
+                ]
+            },
+            {
+                "slot": 11,
+                "events": [{ "status": "Applied" }]
+            }
+        ]
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_apply_result_helpers() {
+    let result = example_result();
+    assert_eq!(result.applied_count(), 2);
+    assert!(result.ensure_all_applied().is_err());
+    assert!(result.ensure_rejected_with(10, 1, 3).is_ok());
+    assert!(result.ensure_rejected_with(10, 1, 4).is_err());
+    assert!(result.ensure_rejected_with(10, 0, 3).is_err());
+    assert!(result.ensure_rejected_with(12, 0, 3).is_err());
+    assert_eq!(
+        result.rejected().collect::<Vec<_>>(),
+        vec![(10, 1, 3, "invalid account")]
+    );
+}
+
+#[test]
+fn test_request_event_wire_format() {
+    use serde_json::json;
+
+    let events = vec![
+        RequestEvent::Deposit(TxDeposit {
+            account: AccountAddress::ONE,
+            amount: 1,
+        }),
+        RequestEvent::WithdrawalAck(TxWithdrawalAck {
+            withdrawal_id: 7,
+            account: AccountAddress::ONE,
+            amount: 2,
+        }),
+        RequestEvent::ForcedTransaction(TxForced {
+            sender: AccountAddress::ONE,
+            payload: "0x0102".into(),
+        }),
+        RequestEvent::Message(TxMessage {
+            sender: "0xab".into(),
+            target: AccountAddress::ONE,
+            nonce: 3,
+            data: "0x".into(),
+        }),
+        RequestEvent::RegisterAsset(TxRegisterAsset {
+            l1_token: "0xcd".into(),
+            symbol: "USDC".into(),
+            decimals: 6,
+        }),
+    ];
+    let one = AccountAddress::ONE.to_long_string();
+    let expected = json!([
+        { "Deposit": { "account": one, "amount": 1 } },
+        { "WithdrawalAck": { "withdrawal_id": 7, "account": one, "amount": 2 } },
+        { "ForcedTransaction": { "sender": one, "payload": "0x0102" } },
+        { "Message": { "sender": "0xab", "target": one, "nonce": 3, "data": "0x" } },
+        { "RegisterAsset": { "l1_token": "0xcd", "symbol": "USDC", "decimals": 6 } },
+    ]);
+
+    assert_eq!(serde_json::to_value(&events).unwrap(), expected);
+    assert_eq!(
+        serde_json::from_value::<Vec<RequestEvent>>(expected).unwrap(),
+        events
+    );
+}
+
+#[test]
+fn test_apply_result_matches_request() {
+    let deposit = RequestEvent::Deposit(TxDeposit {
+        account: AccountAddress::ONE,
+        amount: 1,
+    });
+    let mut request = RequestEngine {
+        parent_payload: 4,
+        max_payload_size: 1001,
+        events: vec![
+            RequestSlot {
+                slot: 10,
+                events: vec![deposit.clone(), deposit.clone()],
+            },
+            RequestSlot {
+                slot: 11,
+                events: vec![deposit],
+            },
+        ],
+    };
+    assert_eq!(request.events_count(), 3);
+    assert!(example_result().ensure_matches(&request).is_ok());
+
+    assert!(request.validate().is_ok());
+
+    request.max_payload_size = 2;
+    assert!(
+        request.validate().is_err(),
+        "Событий больше max_payload_size"
+    );
+    request.max_payload_size = 1001;
+    request.events[1].slot = 10;
+    assert!(request.validate().is_err(), "Повтор слота");
+
+    request.events.pop();
+    assert!(example_result().ensure_matches(&request).is_err());
+}
+
+#[test]
+fn test_request_split() -> Result<()> {
+    let deposit = RequestEvent::Deposit(TxDeposit {
+        account: AccountAddress::ONE,
+        amount: 1,
+    });
+    let slot = |slot, count| RequestSlot {
+        slot,
+        events: vec![deposit.clone(); count],
+    };
+    let mut request = RequestEngine {
+        parent_payload: 4,
+        max_payload_size: 3,
+        events: vec![slot(1, 2), slot(2, 1), slot(3, 0), slot(4, 3), slot(5, 1)],
+    };
+
+    let parts = request.split()?;
+    let slots = parts
+        .iter()
+        .map(|part| part.events.iter().map(|slot| slot.slot).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(slots, [vec![1, 2, 3], vec![4], vec![5]]);
+    for part in &parts {
+        part.validate()?;
+        assert_eq!(part.parent_payload, 4);
+    }
+
+    request.max_payload_size = 1001;
+    assert_eq!(
+        request.split()?,
+        [request.clone()],
+        "Запрос помещается целиком"
+    );
+
+    request.max_payload_size = 2;
+    assert!(request.split().is_err(), "Слот 4 не помещается");
+
+    request.events.clear();
+    assert_eq!(
+        request.split()?.len(),
+        1,
+        "Пустой запрос отправляется как есть"
+    );
+    Ok(())
+}
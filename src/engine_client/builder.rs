@@ -0,0 +1,362 @@
+use eyre::{bail, ensure, Result};
+
+use super::{
+    MvEngine, PayloadId, RequestEngine, RequestEvent, RequestSlot, TxDeposit, TxForced, TxMessage,
+    TxRegisterAsset, TxWithdrawalAck,
+};
+use crate::{
+    aptos::{resolve_account, AccountAddress},
+    slot::SlotAllocator,
+    Slot,
+};
+
+const DEFAULT_MAX_PAYLOAD_SIZE: u64 = 1001;
+
+/// Аккаунт в билдере: адрес или имя профиля Aptos (`"alice"`).
+pub(crate) trait IntoAccount {
+    fn into_account(self) -> Result<AccountAddress>;
+}
+
+impl IntoAccount for AccountAddress {
+    fn into_account(self) -> Result<AccountAddress> {
+        Ok(self)
+    }
+}
+
+impl IntoAccount for &str {
+    fn into_account(self) -> Result<AccountAddress> {
+        resolve_account(self)
+    }
+}
+
+/// Билдер [`RequestEngine`].
+///
+/// ```ignore
+/// let request = RequestEngine::builder()
+///     .slot(|slot| slot.deposit("alice", 10).deposit("bob", 5))
+///     .build(&client, &SLOTS)
+///     .await?;
+/// ```
+///
+/// Номера слотов резервируются через [`SlotAllocator`] при сборке, если не заданы
+/// явно через [`RequestBuilder::slot_at`]. `parent_payload` по умолчанию - `head_payload` ноды.
+#[derive(Debug, Clone)]
+pub(crate) struct RequestBuilder {
+    parent_payload: Option<PayloadId>,
+    max_payload_size: u64,
+    slots: Vec<SlotBuilder>,
+}
+
+/// События одного слота.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SlotBuilder {
+    slot: Option<Slot>,
+    events: Vec<RequestEvent>,
+    /// Пустой слот разрешён только через [`RequestBuilder::empty_slot`].
+    allow_empty: bool,
+    /// Ошибки разбора аккаунтов, возвращаются при сборке.
+    errors: Vec<String>,
+}
+
+impl RequestEngine {
+    pub(crate) fn builder() -> RequestBuilder {
+        RequestBuilder {
+            parent_payload: None,
+            max_payload_size: DEFAULT_MAX_PAYLOAD_SIZE,
+            slots: Vec::new(),
+        }
+    }
+}
+
+impl RequestBuilder {
+    pub(crate) fn parent_payload(mut self, parent_payload: PayloadId) -> Self {
+        self.parent_payload = Some(parent_payload);
+        self
+    }
+
+    pub(crate) fn max_payload_size(mut self, max_payload_size: u64) -> Self {
+        self.max_payload_size = max_payload_size;
+        self
+    }
+
+    /// Слот со следующим свободным номером.
+    pub(crate) fn slot(self, events: impl FnOnce(SlotBuilder) -> SlotBuilder) -> Self {
+        self.push(events(SlotBuilder::default()))
+    }
+
+    /// Слот с заданным номером.
+    pub(crate) fn slot_at(
+        self,
+        slot: Slot,
+        events: impl FnOnce(SlotBuilder) -> SlotBuilder,
+    ) -> Self {
+        self.push(SlotBuilder {
+            slot: Some(slot),
+            ..events(SlotBuilder::default())
+        })
+    }
+
+    /// Слот без событий.
+    pub(crate) fn empty_slot(self) -> Self {
+        self.push(SlotBuilder {
+            allow_empty: true,
+            ..SlotBuilder::default()
+        })
+    }
+
+    fn push(mut self, slot: SlotBuilder) -> Self {
+        self.slots.push(slot);
+        self
+    }
+
+    /// Сборка запроса: резервирование слотов и проверка инвариантов.
+    pub(crate) async fn build<C>(self, client: &C, slots: &SlotAllocator) -> Result<RequestEngine>
+    where
+        C: MvEngine + Sync,
+    {
+        let parent_payload = match self.parent_payload {
+            Some(parent_payload) => parent_payload,
+            None => client.engine_l2info_v1().await?.head_payload,
+        };
+        let count = self.slots.iter().filter(|slot| slot.slot.is_none()).count() as u64;
+        let numbers = if count == 0 {
+            Vec::new()
+        } else {
+            slots.reserve(client, count).await?.collect()
+        };
+        self.assemble(parent_payload, numbers)
+    }
+
+    /// Сборка с уже выделенными номерами для слотов без явного номера.
+    fn assemble(self, parent_payload: PayloadId, numbers: Vec<Slot>) -> Result<RequestEngine> {
+        let mut numbers = numbers.into_iter();
+        let mut events = Vec::with_capacity(self.slots.len());
+        for (index, slot) in self.slots.into_iter().enumerate() {
+            if !slot.errors.is_empty() {
+                bail!("Слот {index}: {}", slot.errors.join("; "));
+            }
+            ensure!(
+                slot.allow_empty || !slot.events.is_empty(),
+                "Слот {index} без событий. Для пустого слота используйте empty_slot()"
+            );
+            let number = match slot.slot {
+                Some(number) => number,
+                None => numbers.next().expect("Номер выделен для каждого слота"),
+            };
+            events.push(RequestSlot {
+                slot: number,
+                events: slot.events,
+            });
+        }
+
+        let request = RequestEngine {
+            parent_payload,
+            max_payload_size: self.max_payload_size,
+            events,
+        };
+        request.validate()?;
+        Ok(request)
+    }
+}
+
+impl SlotBuilder {
+    pub(crate) fn event(mut self, event: RequestEvent) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    pub(crate) fn deposit(self, account: impl IntoAccount, amount: u64) -> Self {
+        self.with_account(account, |account| {
+            RequestEvent::Deposit(TxDeposit { account, amount })
+        })
+    }
+
+    pub(crate) fn withdrawal_ack(
+        self,
+        withdrawal_id: u64,
+        account: impl IntoAccount,
+        amount: u64,
+    ) -> Self {
+        self.with_account(account, |account| {
+            RequestEvent::WithdrawalAck(TxWithdrawalAck {
+                withdrawal_id,
+                account,
+                amount,
+            })
+        })
+    }
+
+    pub(crate) fn forced_transaction(self, sender: impl IntoAccount, payload: &str) -> Self {
+        self.with_account(sender, |sender| {
+            RequestEvent::ForcedTransaction(TxForced {
+                sender,
+                payload: payload.into(),
+            })
+        })
+    }
+
+    pub(crate) fn message(
+        self,
+        sender: &str,
+        target: impl IntoAccount,
+        nonce: u64,
+        data: &str,
+    ) -> Self {
+        self.with_account(target, |target| {
+            RequestEvent::Message(TxMessage {
+                sender: sender.into(),
+                target,
+                nonce,
+                data: data.into(),
+            })
+        })
+    }
+
+    pub(crate) fn register_asset(self, l1_token: &str, symbol: &str, decimals: u8) -> Self {
+        self.event(RequestEvent::RegisterAsset(TxRegisterAsset {
+            l1_token: l1_token.into(),
+            symbol: symbol.into(),
+            decimals,
+        }))
+    }
+
+    fn with_account(
+        mut self,
+        account: impl IntoAccount,
+        event: impl FnOnce(AccountAddress) -> RequestEvent,
+    ) -> Self {
+        match account.into_account() {
+            Ok(account) => self.event(event(account)),
+            Err(err) => {
+                self.errors
+                    .push(format!("событие {}: {err:#}", self.events.len()));
+                self
+            }
+        }
+    }
+}
+
+#[test]
+fn test_request_builder() -> Result<()> {
+    use crate::aptos::builtin_profile;
+
+    let [alice, bob] = [
+        builtin_profile("alice")?.account,
+        builtin_profile("bob")?.account,
+    ];
+    let request = RequestEngine::builder()
+        .slot(|slot| slot.deposit(alice, 10).deposit(bob, 5))
+        .empty_slot()
+        .slot(|slot| slot.deposit(AccountAddress::ONE, 1))
+        .max_payload_size(3)
+        .assemble(7, vec![20, 21, 22])?;
+
+    assert_eq!(request.parent_payload, 7);
+    assert_eq!(request.max_payload_size, 3);
+    assert_eq!(
+        request.events,
+        vec![
+            RequestSlot {
+                slot: 20,
+                events: vec![
+                    RequestEvent::Deposit(TxDeposit {
+                        account: alice,
+                        amount: 10,
+                    }),
+                    RequestEvent::Deposit(TxDeposit {
+                        account: bob,
+                        amount: 5,
+                    }),
+                ],
+            },
+            RequestSlot {
+                slot: 21,
+                events: vec![],
+            },
+            RequestSlot {
+                slot: 22,
+                events: vec![RequestEvent::Deposit(TxDeposit {
+                    account: AccountAddress::ONE,
+                    amount: 1,
+                })],
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_request_builder_invariants() {
+    let build = |builder: RequestBuilder| builder.assemble(0, vec![10, 11, 12]);
+    let [alice, bob] = [AccountAddress::new([1; 32]), AccountAddress::new([2; 32])];
+
+    assert!(
+        build(RequestEngine::builder().slot(|slot| slot)).is_err(),
+        "Пустой слот без empty_slot()"
+    );
+    assert!(
+        build(RequestEngine::builder().slot(|slot| slot.deposit("0xZZ", 1))).is_err(),
+        "Неизвестный аккаунт"
+    );
+    assert!(
+        build(
+            RequestEngine::builder()
+                .slot_at(15, |slot| slot.deposit(alice, 1))
+                .slot(|slot| slot.deposit(alice, 1))
+        )
+        .is_err(),
+        "Слоты должны возрастать"
+    );
+    assert!(
+        build(
+            RequestEngine::builder()
+                .slot(|slot| slot.deposit(alice, 1).deposit(bob, 1))
+                .max_payload_size(1)
+        )
+        .is_err(),
+        "Событий больше max_payload_size"
+    );
+    assert!(build(RequestEngine::builder().max_payload_size(0)).is_err());
+}
+
+#[tokio::test]
+async fn test_request_builder_mock() -> Result<()> {
+    use crate::{
+        aptos::builtin_profile, engine_client::new_client, mock::MockNode, slot::temp_slot_file,
+    };
+
+    let node = MockNode::start().await?;
+    let client = new_client(&node.engine_url, node.jwt)?;
+    let file = temp_slot_file();
+    let slots = SlotAllocator::new(&file);
+    let [alice, eve] = [
+        builtin_profile("alice")?.account,
+        builtin_profile("eve")?.account,
+    ];
+
+    let first = RequestEngine::builder()
+        .slot(|slot| slot.deposit(alice, 1))
+        .slot(|slot| slot.deposit(eve, 1))
+        .build(&client, &slots)
+        .await?;
+    let result = client.engine_apply_all(&first).await?;
+    assert_eq!(first.parent_payload, 0);
+    assert_eq!(
+        first
+            .events
+            .iter()
+            .map(|slot| slot.slot)
+            .collect::<Vec<_>>(),
+        [1, 2]
+    );
+
+    let second = RequestEngine::builder()
+        .empty_slot()
+        .build(&client, &slots)
+        .await?;
+    assert_eq!(second.parent_payload, result.payload_id);
+    assert_eq!(second.events[0].slot, 3);
+
+    std::fs::remove_file(file)?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use eyre::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Slot;
+
+/// Идентификатор payload, который возвращает нода после применения атрибутов.
+pub(crate) type PayloadId = u64;
+
+/// Состояние ноды, которое возвращает `engine_l2Info_v1`.
+///
+/// Десериализация строгая: любое новое или переименованное поле в ответе ноды
+/// приводит к ошибке, чтобы изменение схемы было сразу заметно в тестах.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct L2Info {
+    /// Идентификатор сети.
+    pub(crate) chain_id: u8,
+    /// Последний обработанный слот.
+    pub(crate) head_slot: Slot,
+    /// Идентификатор последнего payload.
+    pub(crate) head_payload: PayloadId,
+    /// Высота последнего блока.
+    pub(crate) block_height: u64,
+    /// Версия леджера после применения последнего payload.
+    pub(crate) ledger_version: u64,
+}
+
+impl L2Info {
+    /// Разбор ответа ноды с указанием полного ответа в ошибке.
+    pub(crate) fn from_value(value: Value) -> Result<Self> {
+        serde_json::from_value(value.clone())
+            .with_context(|| format!("Ответ engine_l2Info_v1 не соответствует схеме: {value:#}"))
+    }
+
+    /// Нода ещё не применила ни одного payload.
+    pub(crate) fn is_genesis(&self) -> bool {
+        self.head_payload == 0
+    }
+
+    /// Ближайший слот, который нода может принять.
+    pub(crate) fn next_slot(&self) -> Slot {
+        self.head_slot + 1
+    }
+
+    /// Проверка, что нода обработала слот `slot`.
+    pub(crate) fn ensure_slot_processed(&self, slot: Slot) -> Result<()> {
+        ensure!(
+            self.head_slot >= slot,
+            "Нода не обработала слот {slot}. head_slot: {}",
+            self.head_slot
+        );
+        Ok(())
+    }
+
+    /// Проверка, что с момента `before` голова сдвинулась ровно на `slots` слотов
+    /// и был создан новый payload.
+    pub(crate) fn ensure_advanced(&self, before: &L2Info, slots: u64) -> Result<()> {
+        ensure!(
+            self.chain_id == before.chain_id,
+            "Изменился chain_id: {} -> {}",
+            before.chain_id,
+            self.chain_id
+        );
+        ensure!(
+            self.head_slot == before.head_slot + slots,
+            "Ожидалось что head_slot сдвинется на {slots}: {} -> {}",
+            before.head_slot,
+            self.head_slot
+        );
+        ensure!(
+            self.head_payload > before.head_payload,
+            "Не был создан новый payload: {} -> {}",
+            before.head_payload,
+            self.head_payload
+        );
+        ensure!(
+            self.block_height >= before.block_height,
+            "Высота блока уменьшилась: {} -> {}",
+            before.block_height,
+            self.block_height
+        );
+        Ok(())
+    }
+}
+
+#[test]
+fn test_l2info_deserialize() -> Result<()> {
+    let info = L2Info::from_value(serde_json::json!({
+        "chain_id": 4,
+        "head_slot": 10,
+        "head_payload": 3,
+        "block_height": 7,
+        "ledger_version": 21,
+    }))?;
+    assert_eq!(info.head_slot, 10);
+    assert_eq!(info.next_slot(), 11);
+    assert!(!info.is_genesis());
+    info.ensure_slot_processed(10)?;
+    assert!(info.ensure_slot_processed(11).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_l2info_schema_drift() {
+    let unknown_field = L2Info::from_value(serde_json::json!({
+        "chain_id": 4,
+        "head_slot": 10,
+        "head_payload": 3,
+        "block_height": 7,
+        "ledger_version": 21,
+        "epoch": 1,
+    }));
+    assert!(
+        format!("{:?}", unknown_field.unwrap_err()).contains("epoch"),
+        "Неизвестное поле должно приводить к ошибке"
+    );
+
+    let missing_field = L2Info::from_value(serde_json::json!({
+        "chain_id": 4,
+        "head_slot": 10,
+    }));
+    assert!(
+        missing_field.is_err(),
+        "Отсутствующее поле должно приводить к ошибке"
+    );
+}
+
+#[test]
+fn test_l2info_advanced() -> Result<()> {
+    let before = L2Info {
+        chain_id: 4,
+        head_slot: 10,
+        head_payload: 3,
+        block_height: 7,
+        ledger_version: 21,
+    };
+    let after = L2Info {
+        head_slot: 12,
+        head_payload: 4,
+        block_height: 8,
+        ledger_version: 25,
+        ..before
+    };
+    after.ensure_advanced(&before, 2)?;
+    assert!(after.ensure_advanced(&before, 1).is_err());
+    assert!(before.ensure_advanced(&before, 0).is_err());
+
+    Ok(())
+}
@@ -3,39 +3,102 @@ use std::fmt::Debug;
 use async_trait::async_trait;
 use eyre::{Context, Result};
 use jsonrpsee::{
-    core::client::ClientT,
-    http_client::{transport::HttpBackend, HttpClient},
+    core::{client::ClientT, ClientError},
+    http_client::{
+        transport::{self, HttpBackend},
+        HttpClient, HttpClientBuilder,
+    },
     rpc_params,
+    types::ErrorObjectOwned,
 };
-use jwt_jsonrpsee::ClientAuth;
+use jwt_jsonrpsee::{ClientAuth, ClientLayer, JwtSecret};
 use serde::Serialize;
 use serde_json::Value;
 use tracing::{debug, instrument};
 
+pub(crate) use attributes::{
+    ApplyAttributesResult, EventResult, RequestEngine, RequestEvent, RequestSlot, SlotResult,
+    TxDeposit, TxForced, TxMessage, TxRegisterAsset, TxWithdrawalAck,
+};
+pub(crate) use l2info::{L2Info, PayloadId};
+pub(crate) use payload_chain::PayloadChain;
+
+mod attributes;
+mod builder;
+mod l2info;
+mod payload_chain;
+
+pub(crate) type EngineClient = HttpClient<ClientAuth<HttpBackend>>;
+
+/// Клиент engine API с JWT авторизацией.
+pub(crate) fn new_client(url: &str, jwt: JwtSecret) -> Result<EngineClient> {
+    HttpClientBuilder::new()
+        .set_http_middleware(tower::ServiceBuilder::new().layer(ClientLayer::new(jwt)))
+        .build(url)
+        .context("Ошибка при попытки создать клиента для service-engine")
+}
+
+/// Ошибка JSON-RPC, с которой нода отклонила запрос целиком.
+pub(crate) fn rpc_error(report: &eyre::Report) -> Option<&ErrorObjectOwned> {
+    match report.downcast_ref::<ClientError>()? {
+        ClientError::Call(err) => Some(err),
+        _ => None,
+    }
+}
+
+/// Нода не ответила: соединение не установлено или истёк таймаут.
+/// Ответы с ошибкой, в том числе отказ в авторизации, сюда не относятся.
+pub(crate) fn is_unreachable(report: &eyre::Report) -> bool {
+    match report.downcast_ref::<ClientError>() {
+        Some(ClientError::RequestTimeout) => true,
+        Some(ClientError::Transport(err)) => !matches!(
+            err.downcast_ref::<transport::Error>(),
+            Some(transport::Error::Rejected { .. })
+        ),
+        _ => false,
+    }
+}
+
 #[async_trait]
 pub(crate) trait MvEngine: ClientT {
     /// Получинеие информации о текущем состоянии ноды.
     #[instrument(level = "debug", skip(self))]
-    async fn engine_l2info_v1(&self) -> Result<Value> {
+    async fn engine_l2info_v1(&self) -> Result<L2Info> {
         debug!("Получинеие информации о текущем состоянии ноды. engine_l2Info_v1: request");
 
-        self.request::<Value, _>("engine_l2Info_v1", rpc_params![])
+        let value = self
+            .request::<Value, _>("engine_l2Info_v1", rpc_params![])
             .await
-            .context("запрос на получения статуса ноды")
+            .context("запрос на получения статуса ноды")?;
+        L2Info::from_value(value)
     }
 
     /// Отправка собыитий.
-    /// Нужен для запросов на депозит
+    /// Нужен для запросов на депозит.
+    /// Принимает [`RequestEngine`] или произвольный json для проверки невалидных запросов.
     #[instrument(level = "debug", skip(self))]
-    async fn engine_applyattributes_v1<T>(&self, value: T) -> Result<Value>
+    async fn engine_applyattributes_v1<T>(&self, value: T) -> Result<ApplyAttributesResult>
     where
         T: Serialize + Send + Debug,
     {
         let value = serde_json::to_value(value)
             .context("Произошла ошибка при преобразовании значения `value` в json")?;
-        self.request::<Value, _>("engine_applyAttributes_v1", rpc_params!(value))
+        let response = self
+            .request::<Value, _>("engine_applyAttributes_v1", rpc_params!(value))
             .await
-            .context("запрос на депозит")
+            .context("запрос на депозит")?;
+        serde_json::from_value(response.clone()).with_context(|| {
+            format!("Ответ engine_applyAttributes_v1 не соответствует схеме: {response:#}")
+        })
+    }
+
+    /// Отправка атрибутов с проверкой, что ответ соответствует запросу
+    /// и все события были применены.
+    async fn engine_apply_all(&self, request: &RequestEngine) -> Result<ApplyAttributesResult> {
+        let result = self.engine_applyattributes_v1(request).await?;
+        result.ensure_matches(request)?;
+        result.ensure_all_applied()?;
+        Ok(result)
     }
 }
-impl MvEngine for HttpClient<ClientAuth<HttpBackend>> {}
+impl MvEngine for EngineClient {}
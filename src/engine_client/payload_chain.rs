@@ -0,0 +1,142 @@
+use eyre::{ContextCompat, Result};
+use tracing::debug;
+
+use super::{ApplyAttributesResult, MvEngine, PayloadId, RequestEngine};
+
+/// Цепочка payload, созданных через `engine_applyAttributes_v1`.
+///
+/// Запоминает `payload_id` каждого ответа и подставляет его в `parent_payload`
+/// следующего запроса. Для проверки форков можно продолжить цепочку
+/// от более старого payload через [`PayloadChain::branch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PayloadChain {
+    /// Все payload цепочки, последний - текущая голова.
+    payloads: Vec<PayloadId>,
+}
+
+impl PayloadChain {
+    pub(crate) fn new(head: PayloadId) -> Self {
+        Self {
+            payloads: vec![head],
+        }
+    }
+
+    /// Цепочка от текущего `head_payload` ноды.
+    pub(crate) async fn from_node<C>(client: &C) -> Result<Self>
+    where
+        C: MvEngine + Sync,
+    {
+        Ok(Self::new(client.engine_l2info_v1().await?.head_payload))
+    }
+
+    pub(crate) fn head(&self) -> PayloadId {
+        *self.payloads.last().expect("Цепочка не бывает пустой")
+    }
+
+    pub(crate) fn payloads(&self) -> &[PayloadId] {
+        &self.payloads
+    }
+
+    /// Добавление payload из ответа ноды в цепочку.
+    pub(crate) fn record(&mut self, result: &ApplyAttributesResult) {
+        debug!("payload {} -> {}", self.head(), result.payload_id);
+        self.payloads.push(result.payload_id);
+    }
+
+    /// Отправка `request` с `parent_payload` равным голове цепочки.
+    pub(crate) async fn apply<C>(
+        &mut self,
+        client: &C,
+        mut request: RequestEngine,
+    ) -> Result<ApplyAttributesResult>
+    where
+        C: MvEngine + Sync,
+    {
+        request.parent_payload = self.head();
+        let result = client.engine_applyattributes_v1(&request).await?;
+        result.ensure_matches(&request)?;
+        self.record(&result);
+        Ok(result)
+    }
+
+    /// Отправка `request`, поделённого на части по `max_payload_size`,
+    /// каждая часть продолжает цепочку от предыдущей.
+    pub(crate) async fn apply_split<C>(
+        &mut self,
+        client: &C,
+        request: RequestEngine,
+    ) -> Result<Vec<ApplyAttributesResult>>
+    where
+        C: MvEngine + Sync,
+    {
+        let mut results = Vec::new();
+        for part in request.split()? {
+            results.push(self.apply(client, part).await?);
+        }
+        Ok(results)
+    }
+
+    /// Новая цепочка, продолжающаяся от `parent` - одного из уже созданных payload.
+    pub(crate) fn branch(&self, parent: PayloadId) -> Result<Self> {
+        let position = self
+            .payloads
+            .iter()
+            .position(|payload| *payload == parent)
+            .with_context(|| format!("payload {parent} не входит в цепочку {:?}", self.payloads))?;
+        Ok(Self {
+            payloads: self.payloads[..=position].to_vec(),
+        })
+    }
+}
+
+#[test]
+fn test_payload_chain_branch() -> Result<()> {
+    let mut chain = PayloadChain::new(3);
+    for payload_id in [4, 5] {
+        chain.record(&ApplyAttributesResult {
+            payload_id,
+            slots: vec![],
+        });
+    }
+    assert_eq!(chain.head(), 5);
+    assert_eq!(chain.payloads(), [3, 4, 5]);
+
+    let fork = chain.branch(4)?;
+    assert_eq!(fork.head(), 4);
+    assert_eq!(fork.payloads(), [3, 4]);
+    assert!(chain.branch(2).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_payload_chain_fork() -> Result<()> {
+    use super::{new_client, RequestSlot};
+    use crate::mock::MockNode;
+
+    let node = MockNode::start().await?;
+    let client = new_client(&node.engine_url, node.jwt)?;
+    let request = |slot| RequestEngine {
+        parent_payload: 0,
+        max_payload_size: 1001,
+        events: vec![RequestSlot {
+            slot,
+            events: vec![],
+        }],
+    };
+
+    let mut chain = PayloadChain::from_node(&client).await?;
+    let first = chain.apply(&client, request(1)).await?.payload_id;
+    let second = chain.apply(&client, request(2)).await?.payload_id;
+    assert_eq!(chain.payloads(), [0, first, second]);
+
+    let mut fork = chain.branch(first)?;
+    let forked = fork.apply(&client, request(3)).await?.payload_id;
+    assert_eq!(fork.payloads(), [0, first, forked]);
+
+    let ledger = node.ledger.lock().unwrap();
+    assert_eq!(ledger.payload_parent(second), Some(first));
+    assert_eq!(ledger.payload_parent(forked), Some(first));
+
+    Ok(())
+}
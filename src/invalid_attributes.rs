@@ -0,0 +1,270 @@
+use eyre::{bail, ensure, ContextCompat, Result};
+use jsonrpsee::types::error::INVALID_PARAMS_CODE;
+use serde_json::{json, Value};
+use tracing::debug;
+use tracing_test::traced_test;
+
+use crate::{
+    engine_client::{rpc_error, MvEngine, PayloadChain, PayloadId, RequestEngine},
+    Slot,
+};
+
+/// Невалидный запрос `engine_applyAttributes_v1` и код ошибки, с которым нода должна
+/// его отклонить. Текст ошибки не проверяется: формулировки ноды не документированы,
+/// сообщения mock-ноды проверяют её собственные тесты.
+#[derive(Debug, Clone)]
+pub(crate) struct InvalidCase {
+    pub(crate) name: String,
+    pub(crate) request: Value,
+    pub(crate) code: i32,
+}
+
+/// Слоты и payload, относительно которых строятся невалидные запросы.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CaseContext {
+    pub(crate) parent_payload: PayloadId,
+    /// Два свободных слота по возрастанию.
+    pub(crate) slots: [Slot; 2],
+    /// Слот, который нода уже обработала.
+    pub(crate) processed_slot: Slot,
+}
+
+impl InvalidCase {
+    fn new(name: impl Into<String>, request: Value, code: i32) -> Self {
+        Self {
+            name: name.into(),
+            request,
+            code,
+        }
+    }
+
+    /// Ошибка десериализации параметров: `-32602`.
+    fn invalid_params(name: impl Into<String>, request: Value) -> Self {
+        Self::new(name, request, INVALID_PARAMS_CODE)
+    }
+
+    /// Матрица невалидных запросов: каждый получен из валидного одной правкой.
+    pub(crate) fn matrix(context: CaseContext) -> Vec<Self> {
+        let [first, second] = context.slots;
+        let valid = valid_request(context.parent_payload, [first, second]);
+        let with = |pointer: &str, value: Value| {
+            let mut request = valid.clone();
+            *request
+                .pointer_mut(pointer)
+                .unwrap_or_else(|| panic!("Нет поля {pointer}")) = value;
+            request
+        };
+        let without = |pointer: &str| {
+            let (parent, field) = pointer.rsplit_once('/').expect("Путь к полю");
+            let mut request = valid.clone();
+            request
+                .pointer_mut(parent)
+                .and_then(Value::as_object_mut)
+                .and_then(|object| object.remove(field))
+                .unwrap_or_else(|| panic!("Нет поля {pointer}"));
+            request
+        };
+        let invalid_attributes = RequestEngine::INVALID_ATTRIBUTES_CODE;
+        let limit = RequestEngine::MAX_PAYLOAD_SIZE_LIMIT;
+
+        let mut cases = vec![
+            // Слоты
+            InvalidCase::new(
+                "повтор слота",
+                with("/events/1/slot", json!(first)),
+                invalid_attributes,
+            ),
+            InvalidCase::new(
+                "убывающие слоты",
+                valid_request(context.parent_payload, [second, first]),
+                invalid_attributes,
+            ),
+            InvalidCase::new(
+                "повторное использование слота",
+                with("/events/0/slot", json!(context.processed_slot)),
+                invalid_attributes,
+            ),
+            // parent_payload
+            InvalidCase::new(
+                "неизвестный parent_payload",
+                with("/parent_payload", json!(u64::MAX)),
+                RequestEngine::UNKNOWN_PARENT_CODE,
+            ),
+            // max_payload_size
+            InvalidCase::new(
+                "нулевой max_payload_size",
+                with("/max_payload_size", json!(0)),
+                invalid_attributes,
+            ),
+            InvalidCase::new(
+                "слишком большой max_payload_size",
+                with("/max_payload_size", json!(limit + 1)),
+                invalid_attributes,
+            ),
+            InvalidCase::new(
+                "payload больше max_payload_size",
+                {
+                    let mut request = with("/max_payload_size", json!(1));
+                    request["events"][1]["events"] = valid["events"][0]["events"].clone();
+                    request
+                },
+                invalid_attributes,
+            ),
+            // Неизвестное событие
+            InvalidCase::invalid_params(
+                "неизвестный тип события",
+                with(
+                    "/events/0/events/0",
+                    json!({ "Mint": { "account": "0x1", "amount": 1 } }),
+                ),
+            ),
+            InvalidCase::invalid_params(
+                "событие без тега",
+                with(
+                    "/events/0/events/0",
+                    json!({ "account": "0x1", "amount": 1 }),
+                ),
+            ),
+        ];
+
+        // Отсутствующие поля
+        for pointer in [
+            "/parent_payload",
+            "/max_payload_size",
+            "/events",
+            "/events/0/slot",
+            "/events/0/events",
+            "/events/0/events/0/Deposit/account",
+            "/events/0/events/0/Deposit/amount",
+        ] {
+            cases.push(InvalidCase::invalid_params(
+                format!("нет поля {pointer}"),
+                without(pointer),
+            ));
+        }
+
+        // Неверные типы
+        for (pointer, value) in [
+            ("/parent_payload", json!("1")),
+            ("/parent_payload", json!(-1)),
+            ("/max_payload_size", json!(1.5)),
+            ("/events", json!({})),
+            ("/events/0", json!([])),
+            ("/events/0/slot", json!("1")),
+            ("/events/0/events", json!(null)),
+            ("/events/0/events/0/Deposit/amount", json!("1")),
+            ("/events/0/events/0/Deposit/amount", json!(-1)),
+            ("/events/0/events/0/Deposit/account", json!(1)),
+        ] {
+            cases.push(InvalidCase::invalid_params(
+                format!("{pointer} = {value}"),
+                with(pointer, value),
+            ));
+        }
+        cases
+    }
+
+    /// Проверка, что нода отклонила запрос с ожидаемым кодом.
+    pub(crate) async fn check<C>(&self, client: &C) -> Result<()>
+    where
+        C: MvEngine + Sync,
+    {
+        let result = client.engine_applyattributes_v1(&self.request).await;
+        let err = match result {
+            Ok(result) => bail!("Запрос принят: {result:?}"),
+            Err(err) => err,
+        };
+        let error = rpc_error(&err).with_context(|| format!("Не ошибка JSON-RPC: {err:#}"))?;
+        let text = format!(
+            "{} {}",
+            error.message(),
+            error.data().map(|data| data.get()).unwrap_or_default()
+        );
+        debug!("{}: [{}] {text}", self.name, error.code());
+        ensure!(
+            error.code() == self.code,
+            "Код ошибки {} вместо {}: {text}",
+            error.code(),
+            self.code
+        );
+        Ok(())
+    }
+}
+
+/// Валидный запрос с двумя слотами, от которого строятся невалидные.
+fn valid_request(parent_payload: PayloadId, [first, second]: [Slot; 2]) -> Value {
+    json!({
+        "parent_payload": parent_payload,
+        "max_payload_size": 1001,
+        "events": [
+            {
+                "slot": first,
+                "events": [{ "Deposit": { "account": "0x1", "amount": 1 } }],
+            },
+            {
+                "slot": second,
+                "events": [],
+            },
+        ],
+    })
+}
+
+#[test]
+fn test_invalid_matrix_valid_base() -> Result<()> {
+    let request: RequestEngine = serde_json::from_value(valid_request(0, [1, 2]))?;
+    request.validate()?;
+
+    let cases = InvalidCase::matrix(CaseContext {
+        parent_payload: 0,
+        slots: [2, 3],
+        processed_slot: 1,
+    });
+    ensure!(cases
+        .iter()
+        .all(|case| case.request != valid_request(0, [2, 3])));
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_invalid_attributes() -> Result<()> {
+    use crate::{engine_client::new_client, engine_url, jwt::get_jwt, SLOTS};
+
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let mut chain = PayloadChain::from_node(&client).await?;
+    let processed_slot = SLOTS.next(&client).await?;
+    let processed = RequestEngine::builder()
+        .slot_at(processed_slot, |slot| slot.deposit("0x1", 1))
+        .build(&client, &SLOTS)
+        .await?;
+    chain
+        .apply(&client, processed)
+        .await?
+        .ensure_all_applied()?;
+
+    let slots = SLOTS.reserve(&client, 2).await?;
+    let context = CaseContext {
+        parent_payload: chain.head(),
+        slots: [*slots.start(), *slots.end()],
+        processed_slot,
+    };
+
+    let mut failed = Vec::new();
+    for case in InvalidCase::matrix(context) {
+        if let Err(err) = case.check(&client).await {
+            failed.push(format!("{}: {err:#}", case.name));
+        }
+    }
+    ensure!(
+        failed.is_empty(),
+        "Нода некорректно отклонила запросы:\n{}",
+        failed.join("\n")
+    );
+
+    debug!("Валидный запрос с теми же слотами принимается");
+    client
+        .engine_applyattributes_v1(valid_request(chain.head(), context.slots))
+        .await?
+        .ensure_all_applied()?;
+    Ok(())
+}
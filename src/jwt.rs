@@ -13,13 +13,16 @@ use tokio::{test, time::sleep};
 use tracing::{debug, info};
 use tracing_test::traced_test;
 
-use crate::URL;
+use crate::{engine_url, mock};
 
 const JWT_PATH: &str = "engine.jwt";
 static JWT: OnceCell<JwtSecret> = OnceCell::new();
 
 pub(crate) async fn get_jwt() -> JwtSecret {
     *JWT.get_or_init(async {
+        if mock::enabled() {
+            return mock::shared().jwt;
+        }
         JwtSecret::from_str(
             &fs::read_to_string(JWT_PATH)
                 .with_context(|| format!("При чтении JWT {JWT_PATH:?} произошла ошибка"))
@@ -33,7 +36,7 @@ pub(crate) async fn get_jwt() -> JwtSecret {
 
 async fn req_status(token: HeaderValue) -> Result<StatusCode> {
     let status = reqwest::Client::new()
-        .get(URL)
+        .get(engine_url())
         .header(reqwest::header::AUTHORIZATION, token)
         .send()
         .await?
@@ -44,9 +47,9 @@ async fn req_status(token: HeaderValue) -> Result<StatusCode> {
 #[tokio::test]
 async fn test_unauth() {
     assert_eq!(
-        reqwest::get(URL)
+        reqwest::get(engine_url())
             .await
-            .with_context(|| format!("Ошибка при обращении на {URL:?}"))
+            .with_context(|| format!("Ошибка при обращении на {:?}", engine_url()))
             .unwrap()
             .status(),
         reqwest::StatusCode::UNAUTHORIZED,
@@ -129,7 +132,7 @@ async fn test_jsonrpsee() -> Result<()> {
     }
 
     // without JWT
-    let client = HttpClientBuilder::new().build(URL).unwrap();
+    let client = HttpClientBuilder::new().build(engine_url()).unwrap();
     let response = client.request::<String, _>("hello", rpc_params![]).await;
     assert!(
         !unwrap_call_auth(response)?,
@@ -139,7 +142,7 @@ async fn test_jsonrpsee() -> Result<()> {
     // with JWT
     let client = HttpClientBuilder::new()
         .set_http_middleware(tower::ServiceBuilder::new().layer(ClientLayer::new(jwt)))
-        .build(URL)
+        .build(engine_url())
         .unwrap();
 
     let response = client.request::<String, _>("hello", rpc_params![]).await;
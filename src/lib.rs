@@ -1,58 +1,69 @@
 #![cfg(test)]
 
-use std::{fs, sync::LazyLock};
-
-use aptos::APTOS_ACCOUNTS;
-use eyre::{Context, Result};
-use jsonrpsee::http_client::HttpClientBuilder;
-use jwt_jsonrpsee::ClientLayer;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use tokio::sync::Mutex;
+use std::{str::FromStr, sync::LazyLock};
+
+use aptos::{aptos_url, AccountAddress, AccountFactory};
+use eyre::{Context, ContextCompat, Result};
+use jsonrpsee::types::error::INVALID_PARAMS_CODE;
+use rand::random;
+use serde_json::json;
 use tracing::debug;
 use tracing_test::traced_test;
 
-use crate::{engine_client::MvEngine, jwt::get_jwt};
+use crate::{
+    deposit::verify_deposits,
+    engine_client::{
+        new_client, rpc_error, EngineClient, EventResult, MvEngine, PayloadChain, PayloadId,
+        RequestEngine, RequestEvent, RequestSlot, TxDeposit,
+    },
+    jwt::get_jwt,
+    slot::{SlotAllocator, LAST_SLOT_FILE},
+    wait::{wait_for_balance, Wait},
+};
 
 pub(crate) mod aptos;
+pub(crate) mod deposit;
+pub(crate) mod deposit_events;
 pub(crate) mod engine_client;
+pub(crate) mod invalid_attributes;
 pub(crate) mod jwt;
+pub(crate) mod mock;
+pub(crate) mod scenario;
+pub(crate) mod slot;
+pub(crate) mod strategy;
+pub(crate) mod wait;
 
 type Slot = u64;
-const LAST_SLOT_FILE: &str = "last.slot";
 
 pub(crate) const URL: &str = "http://localhost:9042";
-static NEXT_SLOL: LazyLock<Mutex<Slot>> = LazyLock::new(|| {
-    let last_slot = fs::read_to_string(LAST_SLOT_FILE)
-        .map(|value| {
-            value
-                .parse::<Slot>()
-                .expect("Не валидное значение в {LAST_SLOT_FILE}")
-        })
-        .unwrap_or_default();
-    Mutex::new(last_slot)
-});
-
-async fn next_slot() -> Slot {
-    let mut slot = NEXT_SLOL.lock().await;
-    *slot += 1;
-    fs::write(LAST_SLOT_FILE, slot.to_string())
-        .with_context(|| format!("Ошибка при записи номера последнего слота {LAST_SLOT_FILE:?}"))
-        .unwrap();
-    *slot
+
+/// Адрес engine API: локальная нода или mock-нода при `TEST_L2_MOCK=1`.
+pub(crate) fn engine_url() -> String {
+    if mock::enabled() {
+        mock::shared().engine_url.clone()
+    } else {
+        URL.to_string()
+    }
+}
+
+static SLOTS: LazyLock<SlotAllocator> = LazyLock::new(|| SlotAllocator::new(LAST_SLOT_FILE));
+
+async fn next_slot(client: &EngineClient) -> Slot {
+    SLOTS
+        .next(client)
+        .await
+        .context("Не удалось выделить слот")
+        .unwrap()
 }
 
 #[traced_test]
 #[tokio::test]
 async fn test_deposit_zero() -> Result<()> {
-    let jwt = get_jwt().await;
-    let client = HttpClientBuilder::new()
-        .set_http_middleware(tower::ServiceBuilder::new().layer(ClientLayer::new(jwt)))
-        .build(URL)
-        .context("Ошибка при попытки создать клиента для service-engine")?;
-    let response: Value = client
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let chain = PayloadChain::from_node(&client).await?;
+    let response = client
         .engine_applyattributes_v1(json!({
-            "parent_payload": 1,
+            "parent_payload": chain.head(),
             "max_payload_size": 1001,
             "events": [
                 {
@@ -78,32 +89,31 @@ async fn test_deposit_zero() -> Result<()> {
 #[traced_test]
 #[tokio::test]
 async fn test_deposit() -> Result<()> {
-    let jwt = get_jwt().await;
-    let client = HttpClientBuilder::new()
-        .set_http_middleware(tower::ServiceBuilder::new().layer(ClientLayer::new(jwt)))
-        .build(URL)
-        .context("Ошибка при попытки создать клиента для service-engine")?;
+    let client = new_client(&engine_url(), get_jwt().await)?;
 
-    debug!("response: {:#?}", client.engine_l2info_v1().await?);
+    let info_before = client.engine_l2info_v1().await?;
+    debug!("l2info: {info_before:#?}");
+    let mut chain = PayloadChain::new(info_before.head_payload);
 
     debug!("Запрос с пустым массивом событий");
-    client
+    let response = client
         .engine_applyattributes_v1(json!({
-            "parent_payload": 0,
+            "parent_payload": chain.head(),
             "events": [],
             "max_payload_size": 1001,
         }))
         .await
         .context("Пустой массив событий")
         .unwrap();
+    chain.record(&response);
 
     debug!("Запрос с пустым массивом событий слота");
-    client
+    let response = client
         .engine_applyattributes_v1(json!({
-            "parent_payload": 0,
+            "parent_payload": chain.head(),
             "events": [
                 {
-                    "slot": next_slot().await,
+                    "slot": next_slot(&client).await,
                     "events":[]
                 }
             ],
@@ -112,21 +122,28 @@ async fn test_deposit() -> Result<()> {
         .await
         .context("Пустой массив событий")
         .unwrap();
+    chain.record(&response);
 
     debug!("Пример запроса через json");
 
-    let response: Value = client.engine_applyattributes_v1(json!({
-            "parent_payload": 1,
+    let response = client.engine_applyattributes_v1(json!({
+            "parent_payload": chain.head(),
             "max_payload_size": 1001,
             "events": [
                 {
-                    "slot": next_slot().await,
+                    "slot": next_slot(&client).await,
                     "events":[
                         {
                             "Deposit":{
                                 "account":"0x0000000000000000000000000000000000000000000000000000000000000001",
                                 "amount":1
                             }
+                        },
+                        {
+                            "Deposit":{
+                                "account":"0x0",
+                                "amount":1
+                            }
                         }
                     ]
                 }
@@ -135,117 +152,232 @@ async fn test_deposit() -> Result<()> {
         .await
         .context("запрос на депозит")?;
     debug!("response: {response:#?}");
+    response.ensure_all_applied()?;
+    chain.record(&response);
 
     debug!("Запрос на пополнение нескольких аккаунтов (engine_applyAttributes_v1)");
-    let response: Value = client
-        .engine_applyattributes_v1(RequestEngine::all().await)
+    let accounts = AccountFactory::new("deposit")
+        .accounts()?
+        .map(|account| account.address());
+    let request = RequestEngine::all(&client, chain.head(), accounts).await?;
+    let last_slot = request
+        .events
+        .iter()
+        .map(|slot| slot.slot)
+        .max()
+        .context("В запросе нет слотов")?;
+    let (response, report) = verify_deposits(&client, &aptos_url(), &request)
         .await
         .context("запрос на депозит")?;
     debug!("response: {response:#?}");
+    debug!("Изменение балансов:\n{report}");
+    response.ensure_all_applied()?;
+    chain.record(&response);
 
-    debug!("response: {:#?}", client.engine_l2info_v1().await?);
+    let info_after = client.engine_l2info_v1().await?;
+    debug!("l2info: {info_after:#?}");
+    assert_eq!(
+        info_after.chain_id, info_before.chain_id,
+        "Нода сменила chain_id во время теста"
+    );
+    info_after.ensure_slot_processed(last_slot)?;
 
     Ok(())
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct RequestEngine {
-    parent_payload: Slot,
-    max_payload_size: Slot,
-    events: Vec<RequestSlot>,
-}
+/// Какие формы адреса нода принимает в `TxDeposit::account`.
+#[traced_test]
+#[tokio::test]
+async fn test_deposit_address_forms() -> Result<()> {
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let mut chain = PayloadChain::from_node(&client).await?;
 
-impl RequestEngine {
-    async fn all() -> Self {
-        Self {
-            parent_payload: 1,
-            max_payload_size: 1001,
-            events: vec![
-                RequestSlot {
-                    slot: next_slot().await,
-                    events: vec![
-                        // Alice
-                        RequestEvent::Deposit(TxDeposit {
-                            account: APTOS_ACCOUNTS[0].into(),
-                            amount: 1,
-                        }),
-                        // Bob
-                        RequestEvent::Deposit(TxDeposit {
-                            account: APTOS_ACCOUNTS[1].into(),
-                            amount: 2,
-                        }),
-                        // Eve
-                        RequestEvent::Deposit(TxDeposit {
-                            account: APTOS_ACCOUNTS[2].into(),
-                            amount: 3,
-                        }),
-                    ],
-                },
-                RequestSlot {
-                    slot: next_slot().await,
-                    events: vec![
-                        // Alice
-                        RequestEvent::Deposit(TxDeposit {
-                            account: APTOS_ACCOUNTS[0].into(),
-                            amount: 1,
-                        }),
-                        // Bob
-                        RequestEvent::Deposit(TxDeposit {
-                            account: APTOS_ACCOUNTS[1].into(),
-                            amount: 2,
-                        }),
-                        // Eve
-                        RequestEvent::Deposit(TxDeposit {
-                            account: APTOS_ACCOUNTS[2].into(),
-                            amount: 3,
-                        }),
-                        // Eve
-                        RequestEvent::Deposit(TxDeposit {
-                            account: APTOS_ACCOUNTS[2].into(),
-                            amount: 4,
-                        }),
-                        // 0x0
-                        RequestEvent::Deposit(TxDeposit {
-                            account: "0".repeat(64),
-                            amount: 1004,
-                        }),
-                        // 0x1
-                        RequestEvent::Deposit(TxDeposit {
-                            account: format!("{:0>64}", "1"),
-                            amount: 1005,
-                        }),
-                    ],
-                },
-                RequestSlot {
-                    slot: next_slot().await,
-                    events: (0..100)
-                        .map(|index| {
-                            // Alice
-                            RequestEvent::Deposit(TxDeposit {
-                                account: APTOS_ACCOUNTS[0].into(),
-                                amount: index,
-                            })
-                        })
-                        .collect::<Vec<_>>(),
-                },
-            ],
-        }
+    let accepted: Vec<fn(u64) -> String> = vec![
+        |value| format!("0x{value:x}"),
+        |value| format!("{value:x}"),
+        |value| format!("0x{value:064x}"),
+        |value| format!("{value:064x}"),
+        |value| format!("0x{value:X}"),
+    ];
+    for form in accepted {
+        let value = random::<u64>() | 1 << 60;
+        let account = form(value);
+        let expected = AccountAddress::from_str(&format!("{value:x}"))?;
+        debug!("{account:?} -> {expected}");
+
+        let before = aptos::balance(&expected).await?;
+        let response = client
+            .engine_applyattributes_v1(json!({
+                "parent_payload": chain.head(),
+                "max_payload_size": 1001,
+                "events": [{
+                    "slot": next_slot(&client).await,
+                    "events": [{ "Deposit": { "account": account, "amount": 3 } }],
+                }],
+            }))
+            .await
+            .with_context(|| format!("Адрес {account:?} должен приниматься"))?;
+        response.ensure_all_applied()?;
+        chain.record(&response);
+        wait_for_balance(&aptos_url(), &expected, before + 3, &Wait::default())
+            .await
+            .with_context(|| format!("Адрес {account:?}"))?;
     }
+
+    // Строка, которая не является адресом, отклоняет только своё событие
+    for account in ["", "0x", "0xZZ", "0x0x1", &format!("0x{}", "1".repeat(65))] {
+        let slot = next_slot(&client).await;
+        let response = client
+            .engine_applyattributes_v1(json!({
+                "parent_payload": chain.head(),
+                "max_payload_size": 1001,
+                "events": [{
+                    "slot": slot,
+                    "events": [{ "Deposit": { "account": account, "amount": 3 } }],
+                }],
+            }))
+            .await
+            .with_context(|| format!("Запрос с адресом {account:?} должен приниматься"))?;
+        response
+            .ensure_rejected_with(slot, 0, EventResult::INVALID_ACCOUNT)
+            .with_context(|| format!("Адрес {account:?}"))?;
+        chain.record(&response);
+    }
+
+    // Не строка - ошибка формата всего запроса
+    for account in [json!(69), json!(null)] {
+        let err = client
+            .engine_applyattributes_v1(json!({
+                "parent_payload": chain.head(),
+                "max_payload_size": 1001,
+                "events": [{
+                    "slot": next_slot(&client).await,
+                    "events": [{ "Deposit": { "account": account, "amount": 3 } }],
+                }],
+            }))
+            .await
+            .err()
+            .with_context(|| format!("Адрес {account} должен отклоняться"))?;
+        let code = rpc_error(&err).map(|err| err.code());
+        assert_eq!(code, Some(INVALID_PARAMS_CODE), "{account}: {err:#}");
+    }
+
+    Ok(())
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct RequestSlot {
-    slot: Slot,
-    events: Vec<RequestEvent>,
+#[traced_test]
+#[tokio::test]
+async fn test_events() -> Result<()> {
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let mut chain = PayloadChain::from_node(&client).await?;
+    // Принудительная транзакция тратит газ отправителя
+    let sender = AccountFactory::new("events").account()?.address();
+    aptos::faucet()
+        .fund_and_wait(&aptos::client(), &sender, 1_000_000)
+        .await?;
+
+    let request = RequestEngine::builder()
+        .parent_payload(chain.head())
+        .slot(|slot| {
+            slot.withdrawal_ack(random(), sender, 1)
+                // 0x1::aptos_account::transfer без аргументов
+                .forced_transaction(
+                    sender,
+                    &format!(
+                        "0x02{:0>64}0d6170746f735f6163636f756e74087472616e736665720000",
+                        "1"
+                    ),
+                )
+                .message(
+                    &format!("0x{}", hex::encode(random::<[u8; 20]>())),
+                    sender,
+                    random(),
+                    "0x68656c6c6f",
+                )
+                .register_asset(
+                    &format!("0x{}", hex::encode(random::<[u8; 20]>())),
+                    "USDC",
+                    6,
+                )
+        })
+        .build(&client, &SLOTS)
+        .await?;
+    let response = chain.apply(&client, request).await?;
+    debug!("response: {response:#?}");
+    response.ensure_all_applied()?;
+
+    Ok(())
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-enum RequestEvent {
-    Deposit(TxDeposit),
+/// Граница `max_payload_size` и деление больших запросов.
+#[traced_test]
+#[tokio::test]
+async fn test_max_payload_size() -> Result<()> {
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let mut chain = PayloadChain::from_node(&client).await?;
+    let account = AccountAddress::new(random());
+    let deposits =
+        |slots: std::ops::RangeInclusive<Slot>, per_slot: usize, max_payload_size| RequestEngine {
+            parent_payload: 0,
+            max_payload_size,
+            events: slots
+                .map(|slot| RequestSlot {
+                    slot,
+                    events: vec![RequestEvent::Deposit(TxDeposit { account, amount: 1 }); per_slot],
+                })
+                .collect(),
+        };
+
+    debug!("Payload ровно в max_payload_size");
+    let request = deposits(SLOTS.reserve(&client, 2).await?, 5, 10);
+    chain.apply(&client, request).await?.ensure_all_applied()?;
+
+    debug!("Payload на одно событие больше max_payload_size");
+    let mut request = deposits(SLOTS.reserve(&client, 1).await?, 11, 10);
+    request.parent_payload = chain.head();
+    let err = client
+        .engine_applyattributes_v1(&request)
+        .await
+        .err()
+        .context("Запрос больше max_payload_size должен отклоняться")?;
+    let code = rpc_error(&err).map(|err| err.code());
+    assert_eq!(
+        code,
+        Some(RequestEngine::INVALID_ATTRIBUTES_CODE),
+        "{err:#}"
+    );
+
+    debug!("Большой запрос делится на несколько");
+    let before = aptos::balance(&account).await?;
+    let request = deposits(SLOTS.reserve(&client, 5).await?, 500, 1001);
+    let results = chain.apply_split(&client, request).await?;
+    assert_eq!(results.len(), 3);
+    for result in &results {
+        result.ensure_all_applied()?;
+    }
+    wait_for_balance(&aptos_url(), &account, before + 2500, &Wait::default()).await?;
+
+    Ok(())
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct TxDeposit {
-    account: String,
-    amount: u64,
+impl RequestEngine {
+    /// Депозиты на аккаунты теста в нескольких слотах.
+    async fn all(
+        client: &EngineClient,
+        parent_payload: PayloadId,
+        [alice, bob, eve]: [AccountAddress; 3],
+    ) -> Result<Self> {
+        RequestEngine::builder()
+            .parent_payload(parent_payload)
+            .slot(|slot| slot.deposit(alice, 1).deposit(bob, 2).deposit(eve, 3))
+            .slot(|slot| {
+                slot.deposit(alice, 1)
+                    .deposit(bob, 2)
+                    .deposit(eve, 3)
+                    .deposit(eve, 4)
+            })
+            .slot(|slot| (0..100).fold(slot, |slot, index| slot.deposit(alice, index)))
+            .build(client, &SLOTS)
+            .await
+    }
 }
@@ -0,0 +1,615 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use eyre::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tracing::debug;
+
+use super::ledger::{DepositRecord, SharedLedger, UserTransaction, GAS_USED};
+use crate::{
+    aptos::{
+        AccountAddress, SignedTransaction, COIN_BALANCE_VIEW, COIN_STORE, FUNGIBLE_STORE,
+        SIGNED_TRANSACTION_BCS,
+    },
+    deposit_events::{DEPOSIT_EVENT, DEPOSIT_EVENTS, DEPOSIT_EVENTS_FIELD},
+};
+
+/// `creation_number` хэндла событий депозитов.
+const DEPOSIT_EVENTS_CREATION_NUMBER: u64 = 4;
+/// Размер страницы событий по умолчанию, как у Aptos REST API.
+const DEFAULT_PAGE_SIZE: usize = 25;
+
+/// Запуск mock Aptos REST API на случайном локальном порту.
+/// Состояние берётся из того же [`SharedLedger`], что и у mock engine API.
+pub(crate) async fn start(ledger: SharedLedger) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .context("Не удалось запустить mock Aptos REST API")?;
+    let addr = listener.local_addr()?;
+    debug!("mock Aptos REST API: {addr}");
+
+    let router = Router::new()
+        .route("/v1", get(ledger_info))
+        .route("/v1/accounts/:account", get(account))
+        .route("/v1/accounts/:account/resources", get(resources))
+        .route("/v1/accounts/:account/modules", get(modules))
+        .route(
+            "/v1/accounts/:account/resource/:resource_type",
+            get(resource),
+        )
+        .route(
+            "/v1/accounts/:account/events/:event_handle/:field",
+            get(events_by_event_handle),
+        )
+        .route("/v1/transactions", post(submit))
+        .route("/v1/transactions/by_hash/:hash", get(transaction_by_hash))
+        .route(
+            "/v1/transactions/by_version/:version",
+            get(transaction_by_version),
+        )
+        .route("/v1/view", post(view))
+        .with_state(ledger);
+    let server = tokio::spawn(async move {
+        axum::serve(listener, router).await.ok();
+    });
+
+    Ok((addr, server))
+}
+
+/// Ошибка в формате Aptos REST API.
+fn error(status: StatusCode, error_code: &str, message: String) -> Response {
+    let body = json!({
+        "message": message,
+        "error_code": error_code,
+        "vm_error_code": null,
+    });
+    (status, Json(body)).into_response()
+}
+
+fn invalid_address(account: &str) -> Response {
+    error(
+        StatusCode::BAD_REQUEST,
+        "invalid_input",
+        format!("Invalid account address: {account:?}"),
+    )
+}
+
+fn account_not_found(account: &AccountAddress) -> Response {
+    error(
+        StatusCode::NOT_FOUND,
+        "account_not_found",
+        format!("Account not found by Address({account})"),
+    )
+}
+
+async fn ledger_info(State(ledger): State<SharedLedger>) -> Json<Value> {
+    let ledger = ledger.lock().unwrap();
+    let info = ledger.info();
+    Json(json!({
+        "chain_id": info.chain_id,
+        "epoch": "1",
+        "ledger_version": info.ledger_version.to_string(),
+        "oldest_ledger_version": "0",
+        "ledger_timestamp": ledger.timestamp_usecs().to_string(),
+        "node_role": "full_node",
+        "oldest_block_height": "0",
+        "block_height": info.block_height.to_string(),
+        "git_hash": "",
+    }))
+}
+
+async fn account(State(ledger): State<SharedLedger>, Path(account): Path<String>) -> Response {
+    let Ok(account) = account.parse::<AccountAddress>() else {
+        return invalid_address(&account);
+    };
+    let ledger = ledger.lock().unwrap();
+    if ledger.balance(&account).is_none() {
+        return account_not_found(&account);
+    }
+    Json(json!({
+        "sequence_number": ledger.sequence_number(&account).to_string(),
+        "authentication_key": account.to_long_string(),
+    }))
+    .into_response()
+}
+
+async fn resources(State(ledger): State<SharedLedger>, Path(account): Path<String>) -> Response {
+    let Ok(account) = account.parse::<AccountAddress>() else {
+        return invalid_address(&account);
+    };
+    let ledger = ledger.lock().unwrap();
+    match ledger.balance(&account) {
+        Some(_) if ledger.is_migrated(&account) => Json(json!([])).into_response(),
+        Some(balance) => Json(json!([coin_store(balance)])).into_response(),
+        None => account_not_found(&account),
+    }
+}
+
+/// Модулей в mock нет: пустой список для существующего аккаунта.
+async fn modules(State(ledger): State<SharedLedger>, Path(account): Path<String>) -> Response {
+    let Ok(account) = account.parse::<AccountAddress>() else {
+        return invalid_address(&account);
+    };
+    match ledger.lock().unwrap().balance(&account) {
+        Some(_) => Json(json!([])).into_response(),
+        None => account_not_found(&account),
+    }
+}
+
+async fn resource(
+    State(ledger): State<SharedLedger>,
+    Path((account, resource_type)): Path<(String, String)>,
+) -> Response {
+    let Ok(account) = account.parse::<AccountAddress>() else {
+        return invalid_address(&account);
+    };
+    let ledger = ledger.lock().unwrap();
+    let resource = match resource_type.as_str() {
+        COIN_STORE if !ledger.is_migrated(&account) => ledger.balance(&account).map(coin_store),
+        FUNGIBLE_STORE => ledger
+            .primary_store_owner(&account)
+            .and_then(|owner| ledger.balance(&owner))
+            .map(fungible_store),
+        DEPOSIT_EVENTS if account == AccountAddress::ONE => {
+            Some(deposit_events(ledger.deposit_events().len()))
+        }
+        _ => None,
+    };
+    match resource {
+        Some(resource) => Json(resource).into_response(),
+        None if ledger.balance(&account).is_none()
+            && ledger.primary_store_owner(&account).is_none() =>
+        {
+            account_not_found(&account)
+        }
+        None => error(
+            StatusCode::NOT_FOUND,
+            "resource_not_found",
+            format!("Resource not found by Address({account}), Struct tag({resource_type})"),
+        ),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Page {
+    start: Option<usize>,
+    limit: Option<usize>,
+}
+
+/// Только хэндл событий депозитов L2 на `0x1`.
+async fn events_by_event_handle(
+    State(ledger): State<SharedLedger>,
+    Path((account, event_handle, field)): Path<(String, String, String)>,
+    Query(page): Query<Page>,
+) -> Response {
+    let Ok(account) = account.parse::<AccountAddress>() else {
+        return invalid_address(&account);
+    };
+    if account != AccountAddress::ONE
+        || event_handle != DEPOSIT_EVENTS
+        || field != DEPOSIT_EVENTS_FIELD
+    {
+        return error(
+            StatusCode::NOT_FOUND,
+            "resource_not_found",
+            format!("Resource not found by Address({account}), Struct tag({event_handle})"),
+        );
+    }
+    let ledger = ledger.lock().unwrap();
+    let events = ledger
+        .deposit_events()
+        .iter()
+        .enumerate()
+        .skip(page.start.unwrap_or_default())
+        .take(page.limit.unwrap_or(DEFAULT_PAGE_SIZE))
+        .map(|(sequence_number, record)| deposit_event(sequence_number, record))
+        .collect::<Vec<_>>();
+    Json(events).into_response()
+}
+
+/// Транзакция в BCS выполняется сразу, но ответ, как у ноды, - `pending_transaction`.
+async fn submit(State(ledger): State<SharedLedger>, headers: HeaderMap, body: Bytes) -> Response {
+    if headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        != Some(SIGNED_TRANSACTION_BCS)
+    {
+        return error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            format!("Expected {SIGNED_TRANSACTION_BCS}"),
+        );
+    }
+    let transaction = match bcs::from_bytes::<SignedTransaction>(&body) {
+        Ok(transaction) => transaction,
+        Err(err) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "invalid_input",
+                format!("Failed to deserialize input into SignedTransaction: {err}"),
+            )
+        }
+    };
+    match ledger.lock().unwrap().submit(transaction) {
+        Ok(transaction) => {
+            let mut body = user_transaction(&transaction);
+            body["type"] = json!("pending_transaction");
+            for field in ["version", "success", "vm_status", "gas_used"] {
+                body.as_object_mut().unwrap().remove(field);
+            }
+            (StatusCode::ACCEPTED, Json(body)).into_response()
+        }
+        Err(status) => error(
+            StatusCode::BAD_REQUEST,
+            "vm_error",
+            format!("Invalid transaction: Type: Validation Code: {status}"),
+        ),
+    }
+}
+
+async fn transaction_by_hash(
+    State(ledger): State<SharedLedger>,
+    Path(hash): Path<String>,
+) -> Response {
+    match ledger.lock().unwrap().transaction_by_hash(&hash) {
+        Some(transaction) => Json(user_transaction(transaction)).into_response(),
+        None => error(
+            StatusCode::NOT_FOUND,
+            "transaction_not_found",
+            format!("Transaction not found by Transaction hash({hash})"),
+        ),
+    }
+}
+
+async fn transaction_by_version(
+    State(ledger): State<SharedLedger>,
+    Path(version): Path<u64>,
+) -> Response {
+    match ledger.lock().unwrap().transaction_by_version(version) {
+        Some(transaction) => Json(user_transaction(transaction)).into_response(),
+        None => error(
+            StatusCode::NOT_FOUND,
+            "transaction_not_found",
+            format!("Transaction not found by Ledger version({version})"),
+        ),
+    }
+}
+
+/// Только `0x1::coin::balance<AptosCoin>`: суммарный баланс, 0 для несуществующего аккаунта.
+async fn view(State(ledger): State<SharedLedger>, Json(request): Json<Value>) -> Response {
+    let account = match (
+        request["function"].as_str(),
+        request["arguments"][0]
+            .as_str()
+            .map(str::parse::<AccountAddress>),
+    ) {
+        (Some(COIN_BALANCE_VIEW), Some(Ok(account))) => account,
+        _ => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "invalid_input",
+                format!("Unsupported view request {request}"),
+            )
+        }
+    };
+    let balance = ledger.lock().unwrap().balance(&account).unwrap_or_default();
+    Json(json!([balance.to_string()])).into_response()
+}
+
+fn user_transaction(transaction: &UserTransaction) -> Value {
+    let raw = &transaction.transaction.raw_txn;
+    json!({
+        "type": "user_transaction",
+        "version": transaction.version.to_string(),
+        "hash": transaction.hash,
+        "success": transaction.success,
+        "vm_status": transaction.vm_status,
+        "sender": raw.sender,
+        "sequence_number": raw.sequence_number.to_string(),
+        "max_gas_amount": raw.max_gas_amount.to_string(),
+        "gas_unit_price": raw.gas_unit_price.to_string(),
+        "gas_used": GAS_USED.to_string(),
+        "expiration_timestamp_secs": raw.expiration_timestamp_secs.to_string(),
+        "payload": {
+            "type": "entry_function_payload",
+            "function": raw.payload.0.to_string(),
+        },
+        "events": [],
+    })
+}
+
+fn coin_store(balance: u64) -> Value {
+    json!({
+        "type": COIN_STORE,
+        "data": {
+            "coin": { "value": balance.to_string() },
+            "frozen": false,
+        },
+    })
+}
+
+fn deposit_events(counter: usize) -> Value {
+    json!({
+        "type": DEPOSIT_EVENTS,
+        "data": {
+            DEPOSIT_EVENTS_FIELD: {
+                "counter": counter.to_string(),
+                "guid": {
+                    "id": {
+                        "addr": AccountAddress::ONE,
+                        "creation_num": DEPOSIT_EVENTS_CREATION_NUMBER.to_string(),
+                    },
+                },
+            },
+        },
+    })
+}
+
+fn deposit_event(sequence_number: usize, record: &DepositRecord) -> Value {
+    json!({
+        "version": record.version.to_string(),
+        "guid": {
+            "creation_number": DEPOSIT_EVENTS_CREATION_NUMBER.to_string(),
+            "account_address": AccountAddress::ONE,
+        },
+        "sequence_number": sequence_number.to_string(),
+        "type": DEPOSIT_EVENT,
+        "data": {
+            "account": record.deposit.account,
+            "amount": record.deposit.amount.to_string(),
+            "slot": record.slot.to_string(),
+        },
+    })
+}
+
+fn fungible_store(balance: u64) -> Value {
+    json!({
+        "type": FUNGIBLE_STORE,
+        "data": {
+            "metadata": { "inner": "0xa" },
+            "balance": balance.to_string(),
+            "frozen": false,
+        },
+    })
+}
+
+/// Депозит через engine API mock-ноды в следующий свободный слот.
+async fn deposit_on_mock(
+    node: &super::MockNode,
+    account: AccountAddress,
+    amount: u64,
+) -> Result<()> {
+    use crate::{
+        engine_client::{new_client, MvEngine, RequestEngine},
+        slot::{temp_slot_file, SlotAllocator},
+    };
+
+    let client = new_client(&node.engine_url, node.jwt)?;
+    let info = node.ledger.lock().unwrap().info();
+    // Номер слота задан явно, файл слотов не создаётся
+    let request = RequestEngine::builder()
+        .parent_payload(info.head_payload)
+        .slot_at(info.next_slot(), |slot| slot.deposit(account, amount))
+        .build(&client, &SlotAllocator::new(temp_slot_file()))
+        .await?;
+    client.engine_apply_all(&request).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_aptos_balance() -> Result<()> {
+    use crate::aptos::{balance_at, builtin_profile};
+
+    let node = super::MockNode::start().await?;
+    let alice = builtin_profile("alice")?.account;
+    assert_eq!(
+        balance_at(&node.aptos_url, &alice).await?,
+        0,
+        "Несуществующий аккаунт"
+    );
+
+    deposit_on_mock(&node, alice, 12).await?;
+    assert_eq!(balance_at(&node.aptos_url, &alice).await?, 12);
+
+    let status = reqwest::get(format!("{}/v1/accounts/0xZZ", node.aptos_url))
+        .await?
+        .status();
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    let info = reqwest::get(format!("{}/v1", node.aptos_url))
+        .await?
+        .json::<Value>()
+        .await?;
+    assert_eq!(info["ledger_version"], "2");
+    assert_eq!(info["block_height"], "1");
+
+    let status = reqwest::get(format!(
+        "{}/v1/accounts/{alice}/resource/0x1::unknown::Resource",
+        node.aptos_url
+    ))
+    .await?
+    .status();
+    assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_aptos_fungible_store() -> Result<()> {
+    use crate::aptos::{balance_report, BalanceReport, BalanceSource};
+
+    let node = super::MockNode::start().await?;
+    let [coin, migrated] = [AccountAddress::new([1; 32]), AccountAddress::new([2; 32])];
+    deposit_on_mock(&node, coin, 5).await?;
+    deposit_on_mock(&node, migrated, 5).await?;
+    node.ledger.lock().unwrap().migrate(migrated);
+
+    let report = balance_report(&node.aptos_url, &coin).await?;
+    assert_eq!(
+        report,
+        BalanceReport {
+            coin_store: Some(5),
+            fungible_store: None,
+            view: Some(5),
+        }
+    );
+    let report = balance_report(&node.aptos_url, &migrated).await?;
+    assert_eq!(
+        report,
+        BalanceReport {
+            coin_store: None,
+            fungible_store: Some(5),
+            view: Some(5),
+        }
+    );
+    assert_eq!(report.total(), (5, BalanceSource::View));
+    assert_eq!(report.discrepancy(), None);
+
+    let missing = balance_report(&node.aptos_url, &AccountAddress::new([3; 32])).await?;
+    assert_eq!(missing.total().0, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_aptos_client() -> Result<()> {
+    use crate::aptos::{aptos_error, AptosClient, ViewRequest};
+
+    let node = super::MockNode::start().await?;
+    let client = AptosClient::new(&node.aptos_url);
+    let alice = AccountAddress::new([1; 32]);
+    deposit_on_mock(&node, alice, 7).await?;
+
+    let info = client.ledger_info().await?;
+    assert_eq!((info.ledger_version, info.block_height), (2, 1));
+    assert_eq!(info.chain_id, node.ledger.lock().unwrap().info().chain_id);
+
+    let account = client.account(&alice).await?;
+    assert_eq!(account.sequence_number, 0);
+    assert_eq!(account.authentication_key, alice.to_long_string());
+    let resources = client.resources(&alice).await?;
+    assert_eq!(resources[0].resource_type, COIN_STORE);
+    assert_eq!(resources[0].data["coin"]["value"], "7");
+    assert!(client.modules(&alice).await?.is_empty());
+    assert_eq!(
+        client.resource(&alice, COIN_STORE).await?,
+        Some(resources[0].clone())
+    );
+    assert_eq!(client.resource(&alice, FUNGIBLE_STORE).await?, None);
+
+    let view = ViewRequest::new(COIN_BALANCE_VIEW)
+        .type_argument("0x1::aptos_coin::AptosCoin")
+        .argument(alice);
+    assert_eq!(client.view(&view).await?, vec![json!("7")]);
+
+    let missing = AccountAddress::new([2; 32]);
+    let err = client.account(&missing).await.unwrap_err();
+    let error = aptos_error(&err).expect("Ошибка Aptos REST API");
+    assert_eq!(
+        (error.status, error.error_code.as_str()),
+        (404, "account_not_found")
+    );
+
+    let err = client
+        .view(&ViewRequest::new("0x1::unknown::view"))
+        .await
+        .unwrap_err();
+    assert_eq!(aptos_error(&err).map(|error| error.status), Some(400));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_aptos_transactions() -> Result<()> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::ledger::GAS_USED;
+    use crate::aptos::{
+        aptos_error, balance_at, builtin_profile, AptosClient, EntryFunction, RawTransaction,
+        Variant, GAS_UNIT_PRICE, MAX_GAS_AMOUNT,
+    };
+
+    let node = super::MockNode::start().await?;
+    let client = AptosClient::new(&node.aptos_url);
+    let [alice, bob] = [
+        builtin_profile("alice")?.local_account()?,
+        builtin_profile("bob")?.local_account()?,
+    ];
+    let deposit = 2 * MAX_GAS_AMOUNT * GAS_UNIT_PRICE;
+    deposit_on_mock(&node, alice.address(), deposit).await?;
+
+    let transaction = client.transfer(&alice, bob.address(), 100).await?;
+    assert_eq!(transaction.gas_used, Some(GAS_USED));
+    assert_eq!(transaction.sequence_number, Some(0));
+    let fee = GAS_USED * GAS_UNIT_PRICE;
+    assert_eq!(
+        balance_at(&node.aptos_url, &alice.address()).await?,
+        deposit - 100 - fee
+    );
+    assert_eq!(balance_at(&node.aptos_url, &bob.address()).await?, 100);
+    assert_eq!(
+        client
+            .transaction_by_version(transaction.version.unwrap())
+            .await?,
+        transaction
+    );
+
+    // Выполнение с ошибкой списывает газ и увеличивает sequence_number
+    let err = client
+        .transfer(&alice, bob.address(), deposit)
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("EINSUFFICIENT_BALANCE"),
+        "{err:#}"
+    );
+    assert_eq!(client.account(&alice.address()).await?.sequence_number, 2);
+    assert_eq!(
+        balance_at(&node.aptos_url, &alice.address()).await?,
+        deposit - 100 - 2 * fee
+    );
+
+    debug!("Отклонение при валидации");
+    let raw = |sequence_number, chain_id| RawTransaction {
+        sender: alice.address(),
+        sequence_number,
+        payload: Variant(EntryFunction::transfer(bob.address(), 1).unwrap()),
+        max_gas_amount: MAX_GAS_AMOUNT,
+        gas_unit_price: GAS_UNIT_PRICE,
+        expiration_timestamp_secs: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 60,
+        chain_id,
+    };
+    for (signed, status) in [
+        (bob.sign(raw(2, 4))?, "INVALID_SIGNATURE"),
+        (alice.sign(raw(1, 4))?, "SEQUENCE_NUMBER_TOO_OLD"),
+        (alice.sign(raw(2, 5))?, "BAD_CHAIN_ID"),
+        (
+            alice.sign(RawTransaction {
+                max_gas_amount: GAS_USED - 1,
+                ..raw(2, 4)
+            })?,
+            "MAX_GAS_UNITS_BELOW_MIN_TRANSACTION_GAS_UNITS",
+        ),
+    ] {
+        let err = client.submit(&signed).await.unwrap_err();
+        let error = aptos_error(&err).expect("Ошибка Aptos REST API");
+        assert_eq!(error.status, 400);
+        assert!(error.message.contains(status), "{error}");
+    }
+    assert_eq!(client.account(&alice.address()).await?.sequence_number, 2);
+
+    Ok(())
+}
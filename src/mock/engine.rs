@@ -0,0 +1,226 @@
+use std::{
+    net::SocketAddr,
+    task::{Context as TaskContext, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{ensure, Context, ContextCompat, Result};
+use futures::{future::BoxFuture, FutureExt};
+use headers::authorization::{Bearer, Credentials};
+use jsonrpsee::{
+    server::{HttpBody, HttpRequest, HttpResponse, RpcModule, Server, ServerHandle},
+    types::ErrorObjectOwned,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use jwt_jsonrpsee::JwtSecret;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Deserialize;
+use tower::{Layer, Service};
+use tracing::debug;
+
+use super::ledger::{RawRequest, SharedLedger};
+use crate::engine_client::RequestEngine;
+
+/// Допустимое расхождение `iat` с текущим временем, как на ноде.
+const IAT_WINDOW_SECS: u64 = 60;
+
+/// Запуск engine API на случайном локальном порту.
+pub(crate) async fn start(
+    jwt: JwtSecret,
+    ledger: SharedLedger,
+) -> Result<(SocketAddr, ServerHandle)> {
+    let server = Server::builder()
+        .set_http_middleware(tower::ServiceBuilder::new().layer(JwtAuthLayer::new(jwt)?))
+        .build("127.0.0.1:0")
+        .await
+        .context("Не удалось запустить mock engine API")?;
+    let addr = server.local_addr()?;
+    debug!("mock engine API: {addr}");
+
+    Ok((addr, server.start(rpc_module(ledger)?)))
+}
+
+fn rpc_module(ledger: SharedLedger) -> Result<RpcModule<SharedLedger>> {
+    let mut module = RpcModule::new(ledger);
+    module.register_method("engine_l2Info_v1", |_, ledger, _| {
+        Ok::<_, ErrorObjectOwned>(ledger.lock().unwrap().info())
+    })?;
+    module.register_method("engine_applyAttributes_v1", |params, ledger, _| {
+        let request: RawRequest = params.one()?;
+        ledger.lock().unwrap().apply_raw(request)
+    })?;
+    Ok(module)
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    iat: u64,
+}
+
+/// Проверка JWT так же, как это делает нода: HS256, `iat` в пределах
+/// [`IAT_WINDOW_SECS`] и `exp`, если он указан.
+#[derive(Clone)]
+struct JwtAuthLayer {
+    key: DecodingKey,
+}
+
+impl JwtAuthLayer {
+    fn new(jwt: JwtSecret) -> Result<Self> {
+        let secret =
+            hex::decode(jwt.to_string()).context("Не удалось преобразовать JWT в байты")?;
+        Ok(Self {
+            key: DecodingKey::from_secret(&secret),
+        })
+    }
+
+    fn authorize(&self, headers: &HeaderMap) -> Result<()> {
+        let header = headers
+            .get(reqwest::header::AUTHORIZATION)
+            .context("Нет заголовка Authorization")?;
+        let token = Bearer::decode(header).context("Ожидался Bearer токен")?;
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.required_spec_claims.clear();
+        validation.leeway = 0;
+        let claims = jsonwebtoken::decode::<Claims>(token.token(), &self.key, &validation)
+            .context("Невалидный токен")?
+            .claims;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        ensure!(
+            claims.iat.abs_diff(now) <= IAT_WINDOW_SECS,
+            "iat {} вне допустимого окна. now: {now}",
+            claims.iat
+        );
+        Ok(())
+    }
+}
+
+impl<S> Layer<S> for JwtAuthLayer {
+    type Service = JwtAuth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JwtAuth {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct JwtAuth<S> {
+    inner: S,
+    layer: JwtAuthLayer,
+}
+
+impl<S> Service<HttpRequest> for JwtAuth<S>
+where
+    S: Service<HttpRequest, Response = HttpResponse>,
+    S::Future: Send + 'static,
+{
+    type Response = HttpResponse;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<HttpResponse, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: HttpRequest) -> Self::Future {
+        if let Err(err) = self.layer.authorize(request.headers()) {
+            debug!("Запрос отклонён: {err:#}");
+            let response = HttpResponse::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(HttpBody::from(format!("{err:#}")))
+                .expect("Ответ собран из валидных данных");
+            return async move { Ok(response) }.boxed();
+        }
+        self.inner.call(request).boxed()
+    }
+}
+
+#[tokio::test]
+async fn test_mock_engine_auth() -> Result<()> {
+    use jwt_jsonrpsee::Claims;
+    use reqwest::header::HeaderValue;
+
+    let node = super::MockNode::start().await?;
+    let secret = hex::decode(node.jwt.to_string())?;
+    let get = |token: Option<HeaderValue>| {
+        let mut request = reqwest::Client::new().get(&node.engine_url);
+        if let Some(token) = token {
+            request = request.header(reqwest::header::AUTHORIZATION, token);
+        }
+        async move { Ok::<_, eyre::Report>(request.send().await?.status()) }
+    };
+
+    assert_eq!(get(None).await?, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        get(Some(node.jwt.to_bearer()?)).await?,
+        StatusCode::METHOD_NOT_ALLOWED,
+        "Валидный токен, но GET не поддерживается"
+    );
+    assert_eq!(
+        get(Some(JwtSecret::new(rand::random()).to_bearer()?)).await?,
+        StatusCode::UNAUTHORIZED,
+        "Токен подписан другим ключом"
+    );
+
+    let mut claims = Claims::default();
+    claims.iat -= IAT_WINDOW_SECS + 10;
+    let stale = jsonwebtoken::encode(
+        &Default::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(&secret),
+    )?;
+    assert_eq!(
+        get(Some(HeaderValue::from_str(&format!(
+            "{} {stale}",
+            Bearer::SCHEME
+        ))?))
+        .await?,
+        StatusCode::UNAUTHORIZED,
+        "iat вне допустимого окна"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_engine_rpc() -> Result<()> {
+    use crate::engine_client::{new_client, MvEngine, RequestEvent, RequestSlot, TxDeposit};
+
+    let node = super::MockNode::start().await?;
+    let client = new_client(&node.engine_url, node.jwt)?;
+
+    let before = client.engine_l2info_v1().await?;
+    assert!(before.is_genesis());
+
+    let request = RequestEngine {
+        parent_payload: before.head_payload,
+        max_payload_size: 1001,
+        events: vec![RequestSlot {
+            slot: before.next_slot(),
+            events: vec![RequestEvent::Deposit(TxDeposit {
+                account: crate::aptos::AccountAddress::new([0x45; 32]),
+                amount: 7,
+            })],
+        }],
+    };
+    let result = client.engine_apply_all(&request).await?;
+    assert_eq!(result.payload_id, 1);
+
+    client
+        .engine_l2info_v1()
+        .await?
+        .ensure_advanced(&before, 1)?;
+    assert_eq!(
+        node.ledger
+            .lock()
+            .unwrap()
+            .balance(&crate::aptos::AccountAddress::new([0x45; 32])),
+        Some(7)
+    );
+
+    Ok(())
+}
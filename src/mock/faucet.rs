@@ -0,0 +1,114 @@
+use std::{
+    net::SocketAddr,
+    sync::LazyLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use eyre::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{net::TcpListener, task::JoinHandle};
+use tracing::debug;
+
+use super::ledger::SharedLedger;
+use crate::aptos::{
+    AccountAddress, EntryFunction, LocalAccount, RawTransaction, Variant, GAS_UNIT_PRICE,
+    MAX_GAS_AMOUNT,
+};
+
+/// Сумма по умолчанию, как у faucet локальной ноды: 1 APT.
+const DEFAULT_AMOUNT: u64 = 100_000_000;
+const FAUCET_KEY: &str = "0x0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f0f";
+
+/// Аккаунт faucet. Адрес получен из ключа, как у аккаунтов Aptos CLI.
+static FAUCET: LazyLock<LocalAccount> =
+    LazyLock::new(|| LocalAccount::from_private_key(FAUCET_KEY).expect("Ключ faucet"));
+
+#[derive(Debug, Deserialize)]
+struct FundRequest {
+    address: AccountAddress,
+    amount: Option<u64>,
+}
+
+/// Запуск mock faucet на случайном локальном порту.
+/// Монеты выпускаются на аккаунт faucet и переводятся транзакцией в [`SharedLedger`].
+pub(crate) async fn start(ledger: SharedLedger) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .context("Не удалось запустить mock faucet")?;
+    let addr = listener.local_addr()?;
+    debug!("mock faucet: {addr}");
+
+    let router = Router::new().route("/fund", post(fund)).with_state(ledger);
+    let server = tokio::spawn(async move {
+        axum::serve(listener, router).await.ok();
+    });
+
+    Ok((addr, server))
+}
+
+async fn fund(State(ledger): State<SharedLedger>, Json(request): Json<FundRequest>) -> Response {
+    let amount = request.amount.unwrap_or(DEFAULT_AMOUNT);
+    let payload = match EntryFunction::transfer(request.address, amount) {
+        Ok(payload) => payload,
+        Err(err) => return (StatusCode::BAD_REQUEST, format!("{err:#}")).into_response(),
+    };
+
+    // Сумма с максимальной комиссией считается до блокировки: паника под ней
+    // отравила бы общий ledger для всех тестов
+    let Some(minted) = amount.checked_add(MAX_GAS_AMOUNT * GAS_UNIT_PRICE) else {
+        return (StatusCode::BAD_REQUEST, "Amount overflows u64 with the fee").into_response();
+    };
+    let mut ledger = ledger.lock().unwrap();
+    if !ledger.mint(FAUCET.address(), minted) {
+        return (StatusCode::BAD_REQUEST, "Faucet balance overflow").into_response();
+    }
+    let raw = RawTransaction {
+        sender: FAUCET.address(),
+        sequence_number: ledger.sequence_number(&FAUCET.address()),
+        payload: Variant(payload),
+        max_gas_amount: MAX_GAS_AMOUNT,
+        gas_unit_price: GAS_UNIT_PRICE,
+        expiration_timestamp_secs: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default()
+            + 60,
+        chain_id: ledger.info().chain_id,
+    };
+    let result = FAUCET
+        .sign(raw)
+        .map_err(|err| format!("{err:#}"))
+        .and_then(|signed| ledger.submit(signed).map_err(str::to_string));
+    match result {
+        Ok(transaction) => Json(json!({ "txn_hashes": [transaction.hash] })).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err).into_response(),
+    }
+}
+
+#[tokio::test]
+async fn test_mock_faucet_overflow() -> Result<()> {
+    use crate::aptos::{aptos_error, balance_at, AptosClient, FaucetClient};
+
+    let node = super::MockNode::start().await?;
+    let faucet = FaucetClient::new(&node.faucet_url);
+    let account = AccountAddress::new(rand::random());
+
+    let err = faucet.fund(&account, u64::MAX).await.unwrap_err();
+    let error = aptos_error(&err).expect("Ошибка faucet");
+    assert_eq!(error.status, 400, "{err:#}");
+
+    debug!("После отказа mock-нода продолжает работать");
+    faucet
+        .fund_and_wait(&AptosClient::new(&node.aptos_url), &account, 5)
+        .await?;
+    assert_eq!(balance_at(&node.aptos_url, &account).await?, 5);
+    Ok(())
+}
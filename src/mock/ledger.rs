@@ -0,0 +1,821 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use jsonrpsee::types::{
+    error::{INVALID_PARAMS_CODE, INVALID_PARAMS_MSG},
+    ErrorObject, ErrorObjectOwned,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    aptos::{primary_store_address, AccountAddress, EntryFunction, SignedTransaction},
+    engine_client::{
+        ApplyAttributesResult, EventResult, L2Info, PayloadId, RequestEngine, RequestEvent,
+        SlotResult, TxDeposit, TxForced, TxMessage, TxRegisterAsset, TxWithdrawalAck,
+    },
+    Slot,
+};
+
+/// Идентификатор сети локальной ноды.
+const CHAIN_ID: u8 = 4;
+/// Газ, который тратит любая пользовательская транзакция.
+pub(crate) const GAS_USED: u64 = 10;
+
+pub(crate) type SharedLedger = Arc<Mutex<Ledger>>;
+
+/// Состояние L2 в памяти, общее для mock-ноды и mock Aptos REST API.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Ledger {
+    head_slot: Slot,
+    head_payload: PayloadId,
+    block_height: u64,
+    ledger_version: u64,
+    /// Время создания последнего блока в микросекундах.
+    timestamp_usecs: u64,
+    balances: HashMap<AccountAddress, u64>,
+    /// Аккаунты, баланс которых хранится в primary fungible store вместо CoinStore.
+    migrated: HashSet<AccountAddress>,
+    /// Родитель каждого созданного payload.
+    payload_parents: HashMap<PayloadId, PayloadId>,
+    /// Слоты из всех принятых запросов.
+    processed_slots: HashSet<Slot>,
+    acknowledged_withdrawals: HashSet<u64>,
+    /// (отправитель, nonce) применённых сообщений.
+    messages: HashSet<(String, u64)>,
+    /// Адреса зарегистрированных токенов L1.
+    assets: HashSet<String>,
+    sequence_numbers: HashMap<AccountAddress, u64>,
+    /// Выполненные пользовательские транзакции в порядке версий.
+    transactions: Vec<UserTransaction>,
+    /// События применённых депозитов в порядке `sequence_number`.
+    deposit_events: Vec<DepositRecord>,
+}
+
+/// Пользовательская транзакция, попавшая в блокчейн.
+#[derive(Debug, Clone)]
+pub(crate) struct UserTransaction {
+    pub(crate) hash: String,
+    pub(crate) version: u64,
+    pub(crate) transaction: SignedTransaction,
+    pub(crate) success: bool,
+    pub(crate) vm_status: String,
+}
+
+/// Применённый депозит, по которому нода выпускает событие.
+#[derive(Debug, Clone)]
+pub(crate) struct DepositRecord {
+    pub(crate) version: u64,
+    pub(crate) slot: Slot,
+    pub(crate) deposit: TxDeposit,
+}
+
+/// Причина отклонения события: (код, сообщение).
+type Rejection = (i64, String);
+
+/// Событие после разбора. `Err` - нода отклонила событие, не применяя его.
+type ParsedEvent = Result<RequestEvent, Rejection>;
+
+/// Поля с адресом аккаунта L2 в событиях запроса.
+const ACCOUNT_FIELDS: [&str; 4] = [
+    "/Deposit/account",
+    "/WithdrawalAck/account",
+    "/ForcedTransaction/sender",
+    "/Message/target",
+];
+
+/// Запрос `engine_applyAttributes_v1`, события которого ещё не разобраны.
+#[derive(Debug, Deserialize)]
+pub(crate) struct RawRequest {
+    parent_payload: PayloadId,
+    max_payload_size: u64,
+    events: Vec<RawSlot>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawSlot {
+    slot: Slot,
+    events: Vec<Value>,
+}
+
+impl Ledger {
+    pub(crate) fn shared() -> SharedLedger {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    pub(crate) fn info(&self) -> L2Info {
+        L2Info {
+            chain_id: CHAIN_ID,
+            head_slot: self.head_slot,
+            head_payload: self.head_payload,
+            block_height: self.block_height,
+            ledger_version: self.ledger_version,
+        }
+    }
+
+    pub(crate) fn timestamp_usecs(&self) -> u64 {
+        self.timestamp_usecs
+    }
+
+    pub(crate) fn payload_parent(&self, payload: PayloadId) -> Option<PayloadId> {
+        self.payload_parents.get(&payload).copied()
+    }
+
+    /// Баланс аккаунта. `None` если аккаунт не существует.
+    pub(crate) fn balance(&self, account: &AccountAddress) -> Option<u64> {
+        self.balances.get(account).copied()
+    }
+
+    /// Перенос баланса аккаунта из CoinStore в primary fungible store.
+    pub(crate) fn migrate(&mut self, account: AccountAddress) {
+        self.migrated.insert(account);
+    }
+
+    pub(crate) fn is_migrated(&self, account: &AccountAddress) -> bool {
+        self.migrated.contains(account)
+    }
+
+    /// Владелец primary fungible store по адресу хранилища.
+    pub(crate) fn primary_store_owner(&self, store: &AccountAddress) -> Option<AccountAddress> {
+        self.migrated
+            .iter()
+            .find(|owner| primary_store_address(owner) == *store)
+            .copied()
+    }
+
+    /// Выпуск новых монет на аккаунт, как у faucet. `false` при переполнении баланса.
+    pub(crate) fn mint(&mut self, account: AccountAddress, amount: u64) -> bool {
+        let balance = self.balances.entry(account).or_default();
+        match balance.checked_add(amount) {
+            Some(sum) => {
+                *balance = sum;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn sequence_number(&self, account: &AccountAddress) -> u64 {
+        self.sequence_numbers
+            .get(account)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn transaction_by_hash(&self, hash: &str) -> Option<&UserTransaction> {
+        self.transactions
+            .iter()
+            .find(|transaction| transaction.hash == hash)
+    }
+
+    pub(crate) fn transaction_by_version(&self, version: u64) -> Option<&UserTransaction> {
+        self.transactions
+            .iter()
+            .find(|transaction| transaction.version == version)
+    }
+
+    pub(crate) fn deposit_events(&self) -> &[DepositRecord] {
+        &self.deposit_events
+    }
+
+    /// Проверка и выполнение транзакции, сразу с записью в блокчейн.
+    /// Ошибка - статус валидации Aptos VM, с которым нода отклоняет транзакцию.
+    /// Неуспешное выполнение, как и в Aptos, списывает газ и увеличивает `sequence_number`.
+    pub(crate) fn submit(
+        &mut self,
+        transaction: SignedTransaction,
+    ) -> Result<UserTransaction, &'static str> {
+        let raw = &transaction.raw_txn;
+        if raw.chain_id != CHAIN_ID {
+            return Err("BAD_CHAIN_ID");
+        }
+        // В mock ключи не ротируются: authentication_key равен адресу
+        if transaction.verify(&raw.sender).is_err() {
+            return Err("INVALID_SIGNATURE");
+        }
+        let sequence_number = self.sequence_number(&raw.sender);
+        if raw.sequence_number < sequence_number {
+            return Err("SEQUENCE_NUMBER_TOO_OLD");
+        }
+        if raw.sequence_number > sequence_number {
+            return Err("SEQUENCE_NUMBER_TOO_NEW");
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        if raw.expiration_timestamp_secs <= now {
+            return Err("TRANSACTION_EXPIRED");
+        }
+        if raw.max_gas_amount < GAS_USED {
+            return Err("MAX_GAS_UNITS_BELOW_MIN_TRANSACTION_GAS_UNITS");
+        }
+        let balance = self.balance(&raw.sender).unwrap_or_default();
+        match raw.max_gas_amount.checked_mul(raw.gas_unit_price) {
+            Some(max_fee) if max_fee <= balance => {}
+            _ => return Err("INSUFFICIENT_BALANCE_FOR_TRANSACTION_FEE"),
+        }
+        let hash = transaction
+            .hash()
+            .map_err(|_| "UNKNOWN_SERIALIZATION_ERROR")?;
+
+        // GAS_USED <= max_gas_amount, поэтому комиссия не больше уже проверенной
+        let sender = raw.sender;
+        let fee = GAS_USED * raw.gas_unit_price;
+        self.balances.insert(sender, balance - fee);
+        let vm_status = match self.execute(sender, &raw.payload.0) {
+            Ok(()) => "Executed successfully".to_string(),
+            Err(status) => status,
+        };
+        *self.sequence_numbers.entry(sender).or_default() += 1;
+        self.ledger_version += 1;
+
+        let transaction = UserTransaction {
+            hash,
+            version: self.ledger_version,
+            success: vm_status == "Executed successfully",
+            vm_status,
+            transaction,
+        };
+        self.transactions.push(transaction.clone());
+        Ok(transaction)
+    }
+
+    /// Из entry функций поддерживается только `0x1::aptos_account::transfer`.
+    fn execute(&mut self, sender: AccountAddress, payload: &EntryFunction) -> Result<(), String> {
+        let function = payload.to_string();
+        let (to, amount) = match (function.as_str(), payload.args.as_slice()) {
+            ("0x1::aptos_account::transfer", [to, amount]) => (
+                bcs::from_bytes::<AccountAddress>(to),
+                bcs::from_bytes::<u64>(amount),
+            ),
+            _ => return Err(format!("FUNCTION_RESOLUTION_FAILURE: {function}")),
+        };
+        let (Ok(to), Ok(amount)) = (to, amount) else {
+            return Err("FAILED_TO_DESERIALIZE_ARGUMENT".to_string());
+        };
+
+        let sender_balance = self.balance(&sender).unwrap_or_default();
+        let Some(sender_balance) = sender_balance.checked_sub(amount) else {
+            return Err("Move abort in 0x1::coin: EINSUFFICIENT_BALANCE(0x10006)".to_string());
+        };
+        self.balances.insert(sender, sender_balance);
+        let recipient = self.balances.entry(to).or_default();
+        match recipient.checked_add(amount) {
+            Some(balance) => {
+                *recipient = balance;
+                Ok(())
+            }
+            None => {
+                self.balances.insert(sender, sender_balance + amount);
+                Err("ARITHMETIC_ERROR".to_string())
+            }
+        }
+    }
+
+    /// Применение атрибутов. Каждый вызов создаёт новый payload и блок.
+    /// Невалидный запрос отклоняется целиком, не меняя состояние.
+    ///
+    /// Модель `max_payload_size` в mock - число транзакций L2, которые создали
+    /// применённые события. Отклонённые события в лимит не входят, поэтому
+    /// [`RequestEngine::payload_size`] даёт для mock оценку сверху.
+    pub(crate) fn apply(
+        &mut self,
+        request: RequestEngine,
+    ) -> Result<ApplyAttributesResult, ErrorObjectOwned> {
+        let slots = request
+            .events
+            .into_iter()
+            .map(|slot| (slot.slot, slot.events.into_iter().map(Ok).collect()))
+            .collect();
+        self.apply_parsed(request.parent_payload, request.max_payload_size, slots)
+    }
+
+    /// Применение запроса в том виде, в каком он пришёл на engine API.
+    pub(crate) fn apply_raw(
+        &mut self,
+        request: RawRequest,
+    ) -> Result<ApplyAttributesResult, ErrorObjectOwned> {
+        let slots = request
+            .events
+            .into_iter()
+            .map(|slot| {
+                let events = slot
+                    .events
+                    .into_iter()
+                    .map(parse_event)
+                    .collect::<Result<_, _>>()?;
+                Ok((slot.slot, events))
+            })
+            .collect::<Result<_, ErrorObjectOwned>>()?;
+        self.apply_parsed(request.parent_payload, request.max_payload_size, slots)
+    }
+
+    fn apply_parsed(
+        &mut self,
+        parent_payload: PayloadId,
+        max_payload_size: u64,
+        slots: Vec<(Slot, Vec<ParsedEvent>)>,
+    ) -> Result<ApplyAttributesResult, ErrorObjectOwned> {
+        self.validate(parent_payload, max_payload_size, &slots)?;
+        let snapshot = self.clone();
+        let version = self.ledger_version;
+
+        let slots = slots
+            .into_iter()
+            .map(|(slot, events)| {
+                self.head_slot = self.head_slot.max(slot);
+                self.processed_slots.insert(slot);
+                SlotResult {
+                    slot,
+                    events: events
+                        .into_iter()
+                        .map(|event| match event {
+                            Ok(event) => self.apply_event(slot, event),
+                            Err((code, message)) => EventResult::Rejected { code, message },
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let transactions = self.ledger_version - version;
+        if transactions > max_payload_size {
+            *self = snapshot;
+            return Err(rpc_error(
+                RequestEngine::INVALID_ATTRIBUTES_CODE,
+                format!("payload size {transactions} exceeds max_payload_size {max_payload_size}"),
+            ));
+        }
+
+        self.head_payload += 1;
+        self.payload_parents
+            .insert(self.head_payload, parent_payload);
+        self.block_height += 1;
+        self.timestamp_usecs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_micros() as u64)
+            .unwrap_or_default();
+        // Метаданные блока тоже занимают версию
+        self.ledger_version += 1;
+
+        Ok(ApplyAttributesResult {
+            payload_id: self.head_payload,
+            slots,
+        })
+    }
+
+    fn validate(
+        &self,
+        parent: PayloadId,
+        max_payload_size: u64,
+        slots: &[(Slot, Vec<ParsedEvent>)],
+    ) -> Result<(), ErrorObjectOwned> {
+        if parent != 0 && !self.payload_parents.contains_key(&parent) {
+            return Err(rpc_error(
+                RequestEngine::UNKNOWN_PARENT_CODE,
+                format!("unknown parent payload {parent}"),
+            ));
+        }
+
+        let invalid = |message| Err(rpc_error(RequestEngine::INVALID_ATTRIBUTES_CODE, message));
+        match max_payload_size {
+            0 => return invalid("max_payload_size must be positive".into()),
+            size if size > RequestEngine::MAX_PAYLOAD_SIZE_LIMIT => {
+                return invalid(format!(
+                    "max_payload_size {size} exceeds limit {}",
+                    RequestEngine::MAX_PAYLOAD_SIZE_LIMIT
+                ))
+            }
+            _ => {}
+        }
+        for pair in slots.windows(2) {
+            let (previous, slot) = (pair[0].0, pair[1].0);
+            if slot == previous {
+                return invalid(format!("duplicate slot {slot}"));
+            }
+            if slot < previous {
+                return invalid(format!("slot {slot} is less than previous slot {previous}"));
+            }
+        }
+        if let Some((slot, _)) = slots
+            .iter()
+            .find(|(slot, _)| self.processed_slots.contains(slot))
+        {
+            return invalid(format!("slot {slot} already processed"));
+        }
+        Ok(())
+    }
+
+    fn apply_event(&mut self, slot: Slot, event: RequestEvent) -> EventResult {
+        let deposit = match &event {
+            RequestEvent::Deposit(deposit) => Some(deposit.clone()),
+            _ => None,
+        };
+        match self.try_apply_event(event) {
+            Ok(()) => {
+                self.ledger_version += 1;
+                if let Some(deposit) = deposit {
+                    self.deposit_events.push(DepositRecord {
+                        version: self.ledger_version,
+                        slot,
+                        deposit,
+                    });
+                }
+                EventResult::Applied
+            }
+            Err((code, message)) => EventResult::Rejected { code, message },
+        }
+    }
+
+    fn try_apply_event(&mut self, event: RequestEvent) -> Result<(), Rejection> {
+        match event {
+            RequestEvent::Deposit(TxDeposit { account, amount }) => {
+                let balance = self.balances.entry(account).or_default();
+                *balance = balance.checked_add(amount).ok_or_else(|| {
+                    (
+                        EventResult::BALANCE_OVERFLOW,
+                        format!("balance {balance} + {amount} overflows u64"),
+                    )
+                })?;
+            }
+            RequestEvent::WithdrawalAck(TxWithdrawalAck { withdrawal_id, .. }) => {
+                if !self.acknowledged_withdrawals.insert(withdrawal_id) {
+                    return Err(duplicate(format!("withdrawal {withdrawal_id}")));
+                }
+            }
+            RequestEvent::ForcedTransaction(TxForced { payload, .. }) => {
+                if parse_hex(&payload)?.is_empty() {
+                    return Err((
+                        EventResult::INVALID_PAYLOAD,
+                        "empty transaction payload".into(),
+                    ));
+                }
+            }
+            RequestEvent::Message(TxMessage {
+                sender,
+                nonce,
+                data,
+                ..
+            }) => {
+                parse_hex(&data)?;
+                let sender = parse_hex(&sender)?;
+                if !self.messages.insert((hex::encode(sender), nonce)) {
+                    return Err(duplicate(format!("message nonce {nonce}")));
+                }
+            }
+            RequestEvent::RegisterAsset(TxRegisterAsset {
+                l1_token, symbol, ..
+            }) => {
+                if symbol.is_empty() {
+                    return Err((EventResult::INVALID_PAYLOAD, "empty asset symbol".into()));
+                }
+                let token = hex::encode(parse_hex(&l1_token)?);
+                if !self.assets.insert(token) {
+                    return Err(duplicate(format!("asset {l1_token}")));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Разбор события как на ноде: строка, которая не является адресом аккаунта L2,
+/// отклоняет только своё событие. Остальные ошибки формата отклоняют запрос целиком.
+fn parse_event(mut event: Value) -> Result<ParsedEvent, ErrorObjectOwned> {
+    let mut rejection = None;
+    for pointer in ACCOUNT_FIELDS {
+        let Some(field) = event.pointer_mut(pointer) else {
+            continue;
+        };
+        let Some(account) = field.as_str() else {
+            continue;
+        };
+        if let Err(err) = account.parse::<AccountAddress>() {
+            rejection = Some((
+                EventResult::INVALID_ACCOUNT,
+                format!("invalid account address {account:?}: {err:#}"),
+            ));
+            *field = json!(AccountAddress::ZERO);
+        }
+    }
+    // Разбор из строки, чтобы ошибка совпадала с ошибкой разбора параметров в jsonrpsee
+    let event = serde_json::from_str(&event.to_string()).map_err(|err| {
+        ErrorObject::owned(
+            INVALID_PARAMS_CODE,
+            INVALID_PARAMS_MSG,
+            Some(err.to_string()),
+        )
+    })?;
+    Ok(rejection.map_or(Ok(event), Err))
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, Rejection> {
+    hex::decode(value.strip_prefix("0x").unwrap_or(value)).map_err(|err| {
+        (
+            EventResult::INVALID_PAYLOAD,
+            format!("invalid hex {value:?}: {err}"),
+        )
+    })
+}
+
+fn rpc_error(code: i32, message: String) -> ErrorObjectOwned {
+    ErrorObject::owned(code, message, None::<()>)
+}
+
+fn duplicate(what: String) -> Rejection {
+    (EventResult::DUPLICATE, format!("{what} already applied"))
+}
+
+#[test]
+fn test_ledger_apply() {
+    use crate::engine_client::RequestSlot;
+
+    let account = "0x45".parse().unwrap();
+    let deposit = |amount| RequestEvent::Deposit(TxDeposit { account, amount });
+
+    let mut ledger = Ledger::default();
+    let result = ledger
+        .apply(RequestEngine {
+            parent_payload: 0,
+            max_payload_size: 1001,
+            events: vec![RequestSlot {
+                slot: 3,
+                events: vec![deposit(1), deposit(2)],
+            }],
+        })
+        .unwrap();
+
+    assert_eq!(result.payload_id, 1);
+    assert_eq!(result.applied_count(), 2);
+    assert_eq!(ledger.balance(&account), Some(3));
+    assert_eq!(ledger.balance(&"0x46".parse().unwrap()), None);
+    assert_eq!(ledger.info().head_slot, 3);
+
+    let result = ledger
+        .apply(RequestEngine {
+            parent_payload: 1,
+            max_payload_size: 1001,
+            events: vec![RequestSlot {
+                slot: 4,
+                events: vec![deposit(u64::MAX - 3), deposit(1)],
+            }],
+        })
+        .unwrap();
+    assert!(result
+        .ensure_rejected_with(4, 1, EventResult::BALANCE_OVERFLOW)
+        .is_ok());
+    assert_eq!(ledger.balance(&account), Some(u64::MAX));
+}
+
+#[test]
+fn test_ledger_apply_raw() {
+    let mut ledger = Ledger::default();
+    let request = |slot, account: Value| {
+        serde_json::from_value::<RawRequest>(json!({
+            "parent_payload": 0,
+            "max_payload_size": 1001,
+            "events": [{
+                "slot": slot,
+                "events": [
+                    { "Deposit": { "account": "0x45", "amount": 1 } },
+                    { "Deposit": { "account": account, "amount": 2 } },
+                    { "ForcedTransaction": { "sender": account, "payload": "0x01" } },
+                    { "Message": { "sender": "0xZZ", "target": "0x45", "nonce": 1, "data": "0x" } },
+                ],
+            }],
+        }))
+        .unwrap()
+    };
+
+    let result = ledger.apply_raw(request(3, json!("0xZZ"))).unwrap();
+    assert_eq!(result.applied_count(), 1);
+    for index in [1, 2] {
+        assert!(result
+            .ensure_rejected_with(3, index, EventResult::INVALID_ACCOUNT)
+            .is_ok());
+    }
+    assert!(result
+        .ensure_rejected_with(3, 3, EventResult::INVALID_PAYLOAD)
+        .is_ok());
+    assert_eq!(ledger.balance(&"0x45".parse().unwrap()), Some(1));
+
+    let before = ledger.info();
+    for account in [json!(69), json!(null)] {
+        let err = ledger.apply_raw(request(4, account.clone())).unwrap_err();
+        assert_eq!(err.code(), INVALID_PARAMS_CODE, "{account}: {err}");
+        assert_eq!(err.message(), INVALID_PARAMS_MSG);
+        assert!(
+            err.data()
+                .is_some_and(|data| data.get().contains("invalid type")),
+            "{account}: {err}"
+        );
+    }
+    for (event, detail) in [
+        (
+            json!({ "Mint": { "account": "0x1", "amount": 1 } }),
+            "unknown variant `Mint`",
+        ),
+        (
+            json!({ "account": "0x1", "amount": 1 }),
+            "unknown variant `account`",
+        ),
+        (
+            json!({ "Deposit": { "amount": 1 } }),
+            "missing field `account`",
+        ),
+    ] {
+        let request = serde_json::from_value::<RawRequest>(json!({
+            "parent_payload": 0,
+            "max_payload_size": 1001,
+            "events": [{ "slot": 4, "events": [event] }],
+        }))
+        .unwrap();
+        let err = ledger.apply_raw(request).unwrap_err();
+        assert_eq!(err.code(), INVALID_PARAMS_CODE, "{err}");
+        assert!(
+            err.data().is_some_and(|data| data.get().contains(detail)),
+            "{detail}: {err}"
+        );
+    }
+    assert_eq!(
+        ledger.info(),
+        before,
+        "Отклонённый запрос не меняет состояние"
+    );
+}
+
+#[test]
+fn test_ledger_events() {
+    use crate::engine_client::RequestSlot;
+
+    let mut ledger = Ledger::default();
+    let mut apply = |slot, events: Vec<RequestEvent>| {
+        ledger
+            .apply(RequestEngine {
+                parent_payload: 0,
+                max_payload_size: 1001,
+                events: vec![RequestSlot { slot, events }],
+            })
+            .unwrap()
+    };
+    let ack = RequestEvent::WithdrawalAck(TxWithdrawalAck {
+        withdrawal_id: 1,
+        account: AccountAddress::ONE,
+        amount: 1,
+    });
+    let message = RequestEvent::Message(TxMessage {
+        sender: "0xab".into(),
+        target: AccountAddress::ONE,
+        nonce: 1,
+        data: "0x00".into(),
+    });
+    let asset = RequestEvent::RegisterAsset(TxRegisterAsset {
+        l1_token: "0xcd".into(),
+        symbol: "USDC".into(),
+        decimals: 6,
+    });
+
+    let result = apply(
+        1,
+        vec![
+            ack.clone(),
+            message.clone(),
+            asset.clone(),
+            RequestEvent::ForcedTransaction(TxForced {
+                sender: AccountAddress::ONE,
+                payload: "0x01".into(),
+            }),
+        ],
+    );
+    assert!(result.ensure_all_applied().is_ok());
+
+    let result = apply(
+        2,
+        vec![
+            ack,
+            message,
+            asset,
+            RequestEvent::ForcedTransaction(TxForced {
+                sender: AccountAddress::ONE,
+                payload: "0xZZ".into(),
+            }),
+        ],
+    );
+    assert_eq!(result.applied_count(), 0);
+    for index in 0..3 {
+        assert!(result
+            .ensure_rejected_with(2, index, EventResult::DUPLICATE)
+            .is_ok());
+    }
+    assert!(result
+        .ensure_rejected_with(2, 3, EventResult::INVALID_PAYLOAD)
+        .is_ok());
+}
+
+#[test]
+fn test_ledger_rejects_request() {
+    use crate::engine_client::RequestSlot;
+
+    let mut ledger = Ledger::default();
+    let request = |parent_payload, max_payload_size, slots: &[Slot]| RequestEngine {
+        parent_payload,
+        max_payload_size,
+        events: slots
+            .iter()
+            .map(|slot| RequestSlot {
+                slot: *slot,
+                events: vec![],
+            })
+            .collect(),
+    };
+    let payload_id = ledger.apply(request(0, 1001, &[1, 2])).unwrap().payload_id;
+    let before = ledger.info();
+
+    let invalid = RequestEngine::INVALID_ATTRIBUTES_CODE;
+    let cases = [
+        (
+            request(5, 1001, &[3]),
+            RequestEngine::UNKNOWN_PARENT_CODE,
+            "unknown parent payload 5".to_string(),
+        ),
+        (
+            request(0, 0, &[3]),
+            invalid,
+            "max_payload_size must be positive".into(),
+        ),
+        (
+            request(0, u64::MAX, &[3]),
+            invalid,
+            format!(
+                "max_payload_size {} exceeds limit {}",
+                u64::MAX,
+                RequestEngine::MAX_PAYLOAD_SIZE_LIMIT
+            ),
+        ),
+        (
+            request(0, 1001, &[3, 3]),
+            invalid,
+            "duplicate slot 3".into(),
+        ),
+        (
+            request(0, 1001, &[4, 3]),
+            invalid,
+            "slot 3 is less than previous slot 4".into(),
+        ),
+        (
+            request(payload_id, 1001, &[2]),
+            invalid,
+            "slot 2 already processed".into(),
+        ),
+    ];
+    for (request, code, message) in cases {
+        let err = ledger.apply(request.clone()).unwrap_err();
+        assert_eq!(err.code(), code, "{request:?}: {err}");
+        assert_eq!(err.message(), message, "{request:?}");
+    }
+    assert_eq!(
+        ledger.info(),
+        before,
+        "Отклонённый запрос не меняет состояние"
+    );
+    assert!(ledger.apply(request(payload_id, 1001, &[3])).is_ok());
+}
+
+#[test]
+fn test_ledger_payload_size() {
+    use crate::engine_client::RequestSlot;
+
+    let mut ledger = Ledger::default();
+    let [full, fresh] = [AccountAddress::new([1; 32]), AccountAddress::new([2; 32])];
+    let request = |events| RequestEngine {
+        parent_payload: 0,
+        max_payload_size: 1,
+        events: vec![RequestSlot { slot: 1, events }],
+    };
+    let deposit = |account, amount| RequestEvent::Deposit(TxDeposit { account, amount });
+    ledger.mint(full, u64::MAX);
+
+    let over = request(vec![deposit(fresh, 1), deposit(fresh, 2)]);
+    let before = ledger.info();
+    let err = ledger.apply(over).unwrap_err();
+    assert_eq!(err.code(), RequestEngine::INVALID_ATTRIBUTES_CODE, "{err}");
+    assert_eq!(err.message(), "payload size 2 exceeds max_payload_size 1");
+    assert_eq!(
+        ledger.info(),
+        before,
+        "Отклонённый запрос не меняет состояние"
+    );
+    assert_eq!(ledger.balance(&fresh), None);
+    assert!(ledger.deposit_events().is_empty());
+
+    // Клиентская оценка считает и отклонённый депозит
+    let rejected = request(vec![deposit(full, 1), deposit(fresh, 1)]);
+    assert!(rejected.validate().is_err());
+    let result = ledger.apply(rejected).unwrap();
+    assert_eq!(result.applied_count(), 1);
+    assert_eq!(ledger.balance(&fresh), Some(1));
+}
@@ -0,0 +1,93 @@
+use std::{
+    env,
+    sync::{mpsc, LazyLock},
+    thread,
+};
+
+use eyre::Result;
+use jsonrpsee::server::ServerHandle;
+use jwt_jsonrpsee::JwtSecret;
+use rand::random;
+use tokio::task::JoinHandle;
+
+use ledger::{Ledger, SharedLedger};
+
+pub(crate) mod aptos;
+pub(crate) mod engine;
+pub(crate) mod faucet;
+pub(crate) mod ledger;
+
+/// При `TEST_L2_MOCK=1` тесты обращаются к встроенной mock-ноде вместо локальной ноды.
+const MOCK_ENV: &str = "TEST_L2_MOCK";
+
+pub(crate) fn enabled() -> bool {
+    env::var(MOCK_ENV).is_ok_and(|value| value == "1")
+}
+
+/// Mock-нода, запущенная в текущем процессе.
+pub(crate) struct MockNode {
+    pub(crate) engine_url: String,
+    pub(crate) aptos_url: String,
+    pub(crate) faucet_url: String,
+    pub(crate) jwt: JwtSecret,
+    pub(crate) ledger: SharedLedger,
+    _engine: ServerHandle,
+    aptos: JoinHandle<()>,
+    faucet: JoinHandle<()>,
+}
+
+impl MockNode {
+    /// Запуск в текущем tokio runtime на случайном порту.
+    /// Сервер останавливается вместе с runtime или при удалении [`MockNode`].
+    pub(crate) async fn start() -> Result<Self> {
+        let jwt = JwtSecret::new(random());
+        let ledger = Ledger::shared();
+        let (engine_addr, engine) = engine::start(jwt, ledger.clone()).await?;
+        let (aptos_addr, aptos) = aptos::start(ledger.clone()).await?;
+        let (faucet_addr, faucet) = faucet::start(ledger.clone()).await?;
+
+        Ok(Self {
+            engine_url: format!("http://{engine_addr}"),
+            aptos_url: format!("http://{aptos_addr}"),
+            faucet_url: format!("http://{faucet_addr}"),
+            jwt,
+            ledger,
+            _engine: engine,
+            aptos,
+            faucet,
+        })
+    }
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        self.aptos.abort();
+        self.faucet.abort();
+    }
+}
+
+/// Общая mock-нода для всех тестов процесса.
+/// У каждого `#[tokio::test]` свой runtime, поэтому нода работает в отдельном потоке.
+static SHARED: LazyLock<MockNode> = LazyLock::new(|| {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("Не удалось создать runtime для mock-ноды")
+            .block_on(async {
+                let node = MockNode::start()
+                    .await
+                    .expect("Не удалось запустить mock-ноду");
+                sender.send(node).ok();
+                futures::future::pending::<()>().await
+            })
+    });
+    receiver
+        .recv()
+        .expect("Поток mock-ноды завершился до запуска")
+});
+
+pub(crate) fn shared() -> &'static MockNode {
+    &SHARED
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{bail, ensure, Context, ContextCompat, Result};
+use serde::{de, Deserialize, Deserializer};
+use serde_json::{json, Value};
+use tracing::{debug, info, instrument};
+use tracing_test::traced_test;
+
+use crate::{
+    aptos::{AccountAddress, AccountFactory},
+    deposit::{balances, wait_for_deltas},
+    engine_client::{rpc_error, ApplyAttributesResult, EventResult, MvEngine, PayloadChain},
+    slot::SlotAllocator,
+    Slot,
+};
+
+/// Каталог со сценариями.
+const SCENARIOS_DIR: &str = "scenarios";
+const DEFAULT_MAX_PAYLOAD_SIZE: u64 = 1001;
+/// Начало имени аккаунта, вместо которого подставляется новый адрес.
+const PLACEHOLDER_PREFIX: &str = "$";
+
+/// Сценарий: последовательность запросов `engine_applyAttributes_v1`
+/// и ожидаемый результат каждого из них.
+///
+/// Номера слотов и `parent_payload` подставляются автоматически, события передаются
+/// ноде как есть, поэтому в сценарии можно описывать и невалидные запросы.
+/// Вместо строк вида `$alice` подставляются адреса новых аккаунтов, свои
+/// при каждом запуске сценария.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Scenario {
+    pub(crate) name: String,
+    pub(crate) steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Step {
+    #[serde(default = "default_max_payload_size")]
+    pub(crate) max_payload_size: u64,
+    /// События каждого слота в формате `RequestEvent`.
+    pub(crate) slots: Vec<Vec<Value>>,
+    #[serde(default)]
+    pub(crate) expect: Expect,
+}
+
+fn default_max_payload_size() -> u64 {
+    DEFAULT_MAX_PAYLOAD_SIZE
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Expect {
+    /// Код ошибки JSON-RPC, если нода должна отклонить запрос целиком.
+    pub(crate) rpc_error: Option<i32>,
+    /// Отклонённые события. Все остальные события должны быть применены.
+    #[serde(default)]
+    pub(crate) rejected: Vec<ExpectRejected>,
+    /// На сколько должен вырасти баланс аккаунтов.
+    #[serde(default)]
+    pub(crate) balances: BTreeMap<ScenarioAccount, u128>,
+    #[serde(default)]
+    pub(crate) l2info: ExpectL2Info,
+}
+
+/// Аккаунт в сценарии: адрес или `$имя` нового аккаунта.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ScenarioAccount {
+    Address(AccountAddress),
+    Placeholder(String),
+}
+
+impl<'de> Deserialize<'de> for ScenarioAccount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        if value.starts_with(PLACEHOLDER_PREFIX) {
+            return Ok(Self::Placeholder(value));
+        }
+        value
+            .parse()
+            .map(Self::Address)
+            .map_err(|err: eyre::Report| de::Error::custom(format!("{err:#}")))
+    }
+}
+
+/// Адреса новых аккаунтов для `$имя` одного запуска сценария.
+#[derive(Debug, Default)]
+struct Placeholders(BTreeMap<String, AccountAddress>);
+
+impl Placeholders {
+    /// Новый аккаунт на каждое имя, встреченное в событиях и ожидаемых балансах.
+    fn generate(scenario: &Scenario) -> Result<Self> {
+        let factory = AccountFactory::new("scenario");
+        let mut names = BTreeSet::new();
+        for step in &scenario.steps {
+            for event in step.slots.iter().flatten() {
+                collect_placeholders(event, &mut names);
+            }
+            names.extend(
+                step.expect
+                    .balances
+                    .keys()
+                    .filter_map(|account| match account {
+                        ScenarioAccount::Placeholder(name) => Some(name.clone()),
+                        ScenarioAccount::Address(_) => None,
+                    }),
+            );
+        }
+        let accounts = names
+            .into_iter()
+            .map(|name| {
+                let address = factory.account()?.address();
+                debug!("{name}: {address}");
+                Ok((name, address))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self(accounts))
+    }
+
+    fn address(&self, account: &ScenarioAccount) -> Result<AccountAddress> {
+        match account {
+            ScenarioAccount::Address(address) => Ok(*address),
+            ScenarioAccount::Placeholder(name) => self
+                .0
+                .get(name)
+                .copied()
+                .with_context(|| format!("Неизвестный аккаунт {name}")),
+        }
+    }
+
+    /// Копия события с адресами вместо `$имя`.
+    fn substitute(&self, value: &Value) -> Value {
+        match value {
+            Value::String(name) => match self.0.get(name) {
+                Some(address) => json!(address),
+                None => value.clone(),
+            },
+            Value::Array(values) => values.iter().map(|value| self.substitute(value)).collect(),
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.substitute(value)))
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
+    }
+}
+
+fn collect_placeholders(value: &Value, names: &mut BTreeSet<String>) {
+    match value {
+        Value::String(name) if name.starts_with(PLACEHOLDER_PREFIX) => {
+            names.insert(name.clone());
+        }
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_placeholders(value, names)),
+        Value::Object(fields) => fields
+            .values()
+            .for_each(|value| collect_placeholders(value, names)),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExpectRejected {
+    /// Индекс слота в шаге.
+    pub(crate) slot: usize,
+    /// Индекс события в слоте.
+    pub(crate) event: usize,
+    pub(crate) code: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExpectL2Info {
+    pub(crate) chain_id: Option<u8>,
+    /// Нода обработала все слоты шага.
+    #[serde(default)]
+    pub(crate) slots_processed: bool,
+}
+
+impl Scenario {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Не удалось прочитать {path:?}"))?;
+        serde_yaml::from_str(&content).with_context(|| format!("Невалидный сценарий {path:?}"))
+    }
+
+    /// Все сценарии каталога в алфавитном порядке.
+    pub(crate) fn load_dir(dir: impl AsRef<Path>) -> Result<Vec<(PathBuf, Self)>> {
+        let dir = dir.as_ref();
+        let mut paths = fs::read_dir(dir)
+            .with_context(|| format!("Не удалось прочитать каталог {dir:?}"))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.retain(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "yaml" || extension == "yml")
+        });
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| Self::load(&path).map(|scenario| (path, scenario)))
+            .collect()
+    }
+
+    /// Выполнение сценария на ноде.
+    #[instrument(level = "debug", skip_all, fields(name = self.name))]
+    pub(crate) async fn run<C>(
+        &self,
+        client: &C,
+        aptos_url: &str,
+        slots: &SlotAllocator,
+    ) -> Result<()>
+    where
+        C: MvEngine + Sync,
+    {
+        let accounts = Placeholders::generate(self)?;
+        let mut chain = PayloadChain::from_node(client).await?;
+        for (index, step) in self.steps.iter().enumerate() {
+            step.run(client, aptos_url, slots, &accounts, &mut chain)
+                .await
+                .with_context(|| format!("Шаг {index} сценария {:?}", self.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl Step {
+    async fn run<C>(
+        &self,
+        client: &C,
+        aptos_url: &str,
+        slots: &SlotAllocator,
+        accounts: &Placeholders,
+        chain: &mut PayloadChain,
+    ) -> Result<()>
+    where
+        C: MvEngine + Sync,
+    {
+        let numbers: Vec<Slot> = if self.slots.is_empty() {
+            Vec::new()
+        } else {
+            slots
+                .reserve(client, self.slots.len() as u64)
+                .await?
+                .collect()
+        };
+        let request = json!({
+            "parent_payload": chain.head(),
+            "max_payload_size": self.max_payload_size,
+            "events": numbers
+                .iter()
+                .zip(&self.slots)
+                .map(|(slot, events)| json!({ "slot": slot, "events": accounts.substitute(&json!(events)) }))
+                .collect::<Vec<_>>(),
+        });
+        debug!("request: {request:#}");
+
+        let expected = self
+            .expect
+            .balances
+            .iter()
+            .map(|(account, delta)| Ok((accounts.address(account)?, *delta)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+        let before = balances(aptos_url, expected.keys().copied()).await?;
+        match (
+            client.engine_applyattributes_v1(&request).await,
+            self.expect.rpc_error,
+        ) {
+            (Ok(result), None) => {
+                chain.record(&result);
+                self.check_events(&numbers, &result)?;
+            }
+            (Err(err), Some(code)) => {
+                let actual = rpc_error(&err).map(|err| err.code());
+                ensure!(
+                    actual == Some(code),
+                    "Ожидалась ошибка JSON-RPC {code}, получено: {err:#}"
+                );
+            }
+            (Ok(result), Some(code)) => {
+                bail!("Ожидалась ошибка JSON-RPC {code}, запрос принят: {result:#?}")
+            }
+            (Err(err), None) => return Err(err),
+        }
+
+        if !expected.is_empty() {
+            let report = wait_for_deltas(aptos_url, &before, &expected).await?;
+            debug!("Изменение балансов:\n{report}");
+        }
+
+        let expect = &self.expect.l2info;
+        if expect.chain_id.is_some() || expect.slots_processed {
+            let info = client.engine_l2info_v1().await?;
+            if let Some(chain_id) = expect.chain_id {
+                ensure!(info.chain_id == chain_id, "chain_id: {}", info.chain_id);
+            }
+            if let (true, Some(last)) = (expect.slots_processed, numbers.last()) {
+                info.ensure_slot_processed(*last)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_events(&self, numbers: &[Slot], response: &ApplyAttributesResult) -> Result<()> {
+        let results = &response.slots;
+        ensure!(
+            results.len() == self.slots.len(),
+            "Ожидались результаты по {} слотам, получено {}",
+            self.slots.len(),
+            results.len()
+        );
+        for (slot_index, (slot, result)) in numbers.iter().zip(results).enumerate() {
+            ensure!(
+                result.slot == *slot && result.events.len() == self.slots[slot_index].len(),
+                "Результат слота {slot_index} не соответствует запросу: {result:?}"
+            );
+            for (event_index, event) in result.events.iter().enumerate() {
+                let expected =
+                    self.expect.rejected.iter().find(|rejected| {
+                        rejected.slot == slot_index && rejected.event == event_index
+                    });
+                match (event, expected) {
+                    (EventResult::Applied, None) => {}
+                    (_, Some(expected)) => {
+                        response.ensure_rejected_with(*slot, event_index, expected.code)?
+                    }
+                    (EventResult::Rejected { code, message }, None) => bail!(
+                        "Слот {slot_index}, событие {event_index} отклонено с кодом {code}: {message}"
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_scenario_files_parse() -> Result<()> {
+    let scenarios = Scenario::load_dir(SCENARIOS_DIR)?;
+    ensure!(!scenarios.is_empty(), "Нет сценариев в {SCENARIOS_DIR:?}");
+    Ok(())
+}
+
+#[test]
+fn test_scenario_schema() {
+    let unknown_field = serde_yaml::from_str::<Scenario>(
+        "
+name: test
+steps:
+  - slots: []
+    expect:
+      balance: {}
+",
+    );
+    assert!(
+        unknown_field.is_err(),
+        "Опечатка в поле сценария должна приводить к ошибке"
+    );
+}
+
+#[test]
+fn test_scenario_placeholders() -> Result<()> {
+    let scenario = serde_yaml::from_str::<Scenario>(
+        r#"
+name: test
+steps:
+  - slots:
+      - - Deposit: { account: "$alice", amount: 1 }
+        - ForcedTransaction: { sender: "$alice", payload: "0x01" }
+        - Deposit: { account: "0xb0b", amount: 2 }
+    expect:
+      balances: { "$alice": 1, "$bob": 0, "0xb0b": 2 }
+"#,
+    )?;
+    let accounts = Placeholders::generate(&scenario)?;
+    assert_eq!(accounts.0.len(), 2);
+
+    let alice = accounts.address(&ScenarioAccount::Placeholder("$alice".into()))?;
+    let bob = accounts.address(&ScenarioAccount::Placeholder("$bob".into()))?;
+    assert_ne!(alice, bob);
+    assert!(accounts
+        .address(&ScenarioAccount::Placeholder("$eve".into()))
+        .is_err());
+
+    let events = accounts.substitute(&json!(scenario.steps[0].slots[0]));
+    assert_eq!(
+        events,
+        json!([
+            { "Deposit": { "account": alice, "amount": 1 } },
+            { "ForcedTransaction": { "sender": alice, "payload": "0x01" } },
+            { "Deposit": { "account": "0xb0b", "amount": 2 } },
+        ])
+    );
+
+    let other = Placeholders::generate(&scenario)?;
+    assert_ne!(
+        other.0["$alice"], alice,
+        "Каждый запуск получает новые аккаунты"
+    );
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_scenarios() -> Result<()> {
+    use crate::{aptos::aptos_url, engine_client::new_client, engine_url, jwt::get_jwt, SLOTS};
+
+    let client = new_client(&engine_url(), get_jwt().await)?;
+    let mut failed = Vec::new();
+    for (path, scenario) in Scenario::load_dir(SCENARIOS_DIR)? {
+        info!("Сценарий {path:?}: {}", scenario.name);
+        if let Err(err) = scenario.run(&client, &aptos_url(), &SLOTS).await {
+            failed.push(format!("{path:?}: {err:?}"));
+        }
+    }
+    ensure!(
+        failed.is_empty(),
+        "Не прошли сценарии:\n{}",
+        failed.join("\n")
+    );
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::RangeInclusive,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use eyre::{ensure, Context, ContextCompat, Result};
+use fs4::fs_std::FileExt;
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    engine_client::{is_unreachable, MvEngine},
+    Slot,
+};
+
+/// Файл с последним зарезервированным слотом, общий для всех тестовых процессов.
+pub(crate) const LAST_SLOT_FILE: &str = "last.slot";
+
+/// Содержимое [`LAST_SLOT_FILE`]: `<последний зарезервированный слот> [<head_slot ноды>]`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct SlotState {
+    reserved: Slot,
+    /// `head_slot` ноды на момент резервирования. Нет, если нода была недоступна.
+    node_head: Option<Slot>,
+}
+
+impl SlotState {
+    /// Первый свободный слот с учётом состояния ноды.
+    /// `node_head` должен быть получен под блокировкой файла, иначе устаревший ответ
+    /// медленного процесса выглядел бы как сброс ноды.
+    fn next_free(saved: Option<SlotState>, node_head: Option<Slot>) -> Slot {
+        let last = match (saved, node_head) {
+            (None, head) => head.unwrap_or_default(),
+            (Some(saved), None) => saved.reserved,
+            (Some(saved), Some(head)) => match saved.node_head {
+                Some(saved_head) if head < saved_head => {
+                    info!("head_slot ноды уменьшился {saved_head} -> {head}. Нода была сброшена");
+                    head
+                }
+                // Файл от старой версии: номер слота мог устареть, верна только нода
+                None => head,
+                // Зарезервированные, но ещё не отправленные слоты не считаются сбросом
+                Some(_) => saved.reserved.max(head),
+            },
+        };
+        last + 1
+    }
+}
+
+impl FromStr for SlotState {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut parts = value.split_whitespace();
+        let reserved = parts
+            .next()
+            .context("Пустое значение")?
+            .parse()
+            .context("Не валидный номер слота")?;
+        let node_head = parts
+            .next()
+            .map(|head| head.parse().context("Не валидный head_slot"))
+            .transpose()?;
+        ensure!(parts.next().is_none(), "Лишние значения");
+
+        Ok(Self {
+            reserved,
+            node_head,
+        })
+    }
+}
+
+impl fmt::Display for SlotState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.node_head {
+            Some(head) => write!(f, "{} {head}", self.reserved),
+            None => write!(f, "{}", self.reserved),
+        }
+    }
+}
+
+/// Резервирование номеров слотов, синхронизированное с `head_slot` ноды.
+///
+/// Перед каждым резервированием запрашивается `engine_l2Info_v1`: если кто-то другой уже
+/// отправил слоты, выдача продолжается после них, а если `head_slot` уменьшился,
+/// значит нода была сброшена и выдача начинается заново с её `head_slot`.
+/// Запрос к ноде и резервирование выполняются под эксклюзивной блокировкой файла, поэтому
+/// параллельные тестовые процессы никогда не получают одинаковые слоты.
+#[derive(Debug)]
+pub(crate) struct SlotAllocator {
+    file: PathBuf,
+}
+
+impl SlotAllocator {
+    pub(crate) fn new(file: impl Into<PathBuf>) -> Self {
+        Self { file: file.into() }
+    }
+
+    /// Следующий свободный слот.
+    pub(crate) async fn next<C>(&self, client: &C) -> Result<Slot>
+    where
+        C: MvEngine + Sync,
+    {
+        Ok(*self.reserve(client, 1).await?.start())
+    }
+
+    /// Резервирование `count` идущих подряд слотов.
+    /// `head_slot` запрашивается у ноды под блокировкой файла.
+    #[instrument(level = "debug", skip(client))]
+    pub(crate) async fn reserve<C>(&self, client: &C, count: u64) -> Result<RangeInclusive<Slot>>
+    where
+        C: MvEngine + Sync,
+    {
+        let path = self.file.clone();
+        let file = tokio::task::spawn_blocking(move || lock_file(&path))
+            .await
+            .context("Поток блокировки файла слотов завершился с ошибкой")??;
+        let node_head = match client.engine_l2info_v1().await {
+            Ok(info) => Some(info.head_slot),
+            Err(err) if is_unreachable(&err) => {
+                warn!(
+                    "Нода недоступна, номер слота берётся из {:?}: {err:#}",
+                    self.file
+                );
+                None
+            }
+            Err(err) => return Err(err.wrap_err("Не удалось получить head_slot ноды")),
+        };
+        self.reserve_locked(file, node_head, count)
+    }
+
+    #[cfg(test)]
+    fn reserve_in_file(&self, node_head: Option<Slot>, count: u64) -> Result<RangeInclusive<Slot>> {
+        self.reserve_locked(lock_file(&self.file)?, node_head, count)
+    }
+
+    /// Резервирование в заблокированном файле. Блокировка снимается при удалении `file`.
+    fn reserve_locked(
+        &self,
+        mut file: File,
+        node_head: Option<Slot>,
+        count: u64,
+    ) -> Result<RangeInclusive<Slot>> {
+        ensure!(count > 0, "Нельзя зарезервировать 0 слотов");
+
+        let saved = read_state(&mut file)
+            .with_context(|| format!("Не валидное значение в {:?}", self.file))?;
+        let start = SlotState::next_free(saved, node_head);
+        let end = start + count - 1;
+
+        let state = SlotState {
+            reserved: end,
+            // Без ответа ноды сохранённый head_slot остаётся прежним
+            node_head: node_head.or_else(|| saved.and_then(|saved| saved.node_head)),
+        };
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(state.to_string().as_bytes())
+            .with_context(|| {
+                format!("Ошибка при записи номера последнего слота {:?}", self.file)
+            })?;
+        debug!("Зарезервированы слоты {start}..={end}");
+
+        Ok(start..=end)
+    }
+}
+
+/// Открытие файла слотов с эксклюзивной блокировкой. Блокирует поток до её получения.
+fn lock_file(path: &PathBuf) -> Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("Не удалось открыть {path:?}"))?;
+    file.lock_exclusive()
+        .with_context(|| format!("Не удалось заблокировать {path:?}"))?;
+    Ok(file)
+}
+
+fn read_state(file: &mut File) -> Result<Option<SlotState>> {
+    let mut value = String::new();
+    file.read_to_string(&mut value)?;
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    value.parse().map(Some)
+}
+
+/// Отдельный файл слотов для тестов с собственной mock-нодой.
+pub(crate) fn temp_slot_file() -> PathBuf {
+    std::env::temp_dir().join(format!("test_l2_{}.slot", rand::random::<u64>()))
+}
+
+#[test]
+fn test_slot_state() -> Result<()> {
+    let state = |reserved, node_head| SlotState {
+        reserved,
+        node_head,
+    };
+
+    assert_eq!("12".parse::<SlotState>()?, state(12, None));
+    assert_eq!("12 10\n".parse::<SlotState>()?, state(12, Some(10)));
+    assert!("12 10 1".parse::<SlotState>().is_err());
+    assert_eq!(state(12, Some(10)).to_string(), "12 10");
+
+    assert_eq!(SlotState::next_free(None, None), 1);
+    assert_eq!(SlotState::next_free(None, Some(10)), 11);
+    assert_eq!(SlotState::next_free(Some(state(41, None)), None), 42);
+    // Зарезервированные другими процессами слоты
+    assert_eq!(
+        SlotState::next_free(Some(state(15, Some(10))), Some(10)),
+        16
+    );
+    // Слоты отправлены кем-то другим
+    assert_eq!(
+        SlotState::next_free(Some(state(15, Some(10))), Some(20)),
+        21
+    );
+    // Сброс ноды
+    assert_eq!(SlotState::next_free(Some(state(15, Some(10))), Some(2)), 3);
+    // Файл от старой версии без head_slot
+    assert_eq!(SlotState::next_free(Some(state(500, None)), Some(2)), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_slot_reserve_concurrent() -> Result<()> {
+    const WORKERS: u64 = 8;
+    const RESERVATIONS: u64 = 20;
+
+    let file = temp_slot_file();
+    let mut ranges = std::thread::scope(|scope| {
+        let workers = (0..WORKERS)
+            .map(|worker| {
+                let allocator = SlotAllocator::new(&file);
+                scope.spawn(move || {
+                    (0..RESERVATIONS)
+                        .map(|_| allocator.reserve_in_file(Some(5), worker + 1))
+                        .collect::<Result<Vec<_>>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect::<Result<Vec<_>>>()
+    })?
+    .concat();
+
+    ranges.sort_by_key(|range| *range.start());
+    assert_eq!(*ranges[0].start(), 6);
+    for pair in ranges.windows(2) {
+        assert_eq!(
+            pair[0].end() + 1,
+            *pair[1].start(),
+            "Диапазоны пересекаются или между ними есть пропуск: {pair:?}"
+        );
+    }
+
+    std::fs::remove_file(file)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_slot_allocator_node() -> Result<()> {
+    use crate::{
+        engine_client::{new_client, RequestEngine, RequestSlot},
+        mock::MockNode,
+    };
+
+    let node = MockNode::start().await?;
+    let client = new_client(&node.engine_url, node.jwt)?;
+    let file = temp_slot_file();
+
+    std::fs::write(&file, "500")?;
+
+    let allocator = SlotAllocator::new(&file);
+    assert_eq!(
+        allocator.next(&client).await?,
+        1,
+        "Сохранённый слот устарел"
+    );
+    assert_eq!(allocator.reserve(&client, 3).await?, 2..=4);
+
+    client
+        .engine_apply_all(&RequestEngine {
+            parent_payload: 0,
+            max_payload_size: 1001,
+            events: vec![RequestSlot {
+                slot: 7,
+                events: vec![],
+            }],
+        })
+        .await?;
+    assert_eq!(
+        allocator.next(&client).await?,
+        8,
+        "Слоты отправленные другим клиентом"
+    );
+    assert_eq!(std::fs::read_to_string(&file)?, "8 7");
+
+    std::fs::remove_file(file)?;
+    Ok(())
+}
+
+/// Параллельные резервирования в одном runtime с запросом к ноде под блокировкой.
+#[tokio::test]
+async fn test_slot_allocator_concurrent() -> Result<()> {
+    use futures::future::try_join_all;
+
+    use crate::{engine_client::new_client, mock::MockNode};
+
+    let node = MockNode::start().await?;
+    let client = new_client(&node.engine_url, node.jwt)?;
+    let file = temp_slot_file();
+    let allocators = (0..8)
+        .map(|_| SlotAllocator::new(&file))
+        .collect::<Vec<_>>();
+
+    let mut ranges = try_join_all(
+        allocators
+            .iter()
+            .enumerate()
+            .map(|(index, allocator)| allocator.reserve(&client, index as u64 + 1)),
+    )
+    .await?;
+    ranges.sort_by_key(|range| *range.start());
+    assert_eq!(*ranges[0].start(), 1);
+    for pair in ranges.windows(2) {
+        assert_eq!(pair[0].end() + 1, *pair[1].start(), "{pair:?}");
+    }
+
+    std::fs::remove_file(file)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_slot_allocator_fallback() -> Result<()> {
+    use crate::{engine_client::new_client, mock::MockNode};
+    use jwt_jsonrpsee::JwtSecret;
+
+    let client = new_client("http://127.0.0.1:1", JwtSecret::new(rand::random()))?;
+    let file = temp_slot_file();
+
+    let allocator = SlotAllocator::new(&file);
+    assert_eq!(allocator.next(&client).await?, 1, "Нет ни ноды, ни файла");
+
+    std::fs::write(&file, "41")?;
+    assert_eq!(allocator.next(&client).await?, 42);
+    assert_eq!(allocator.reserve(&client, 2).await?, 43..=44);
+
+    debug!("Нода доступна, но отказывает в авторизации");
+    let node = MockNode::start().await?;
+    let client = new_client(&node.engine_url, JwtSecret::new(rand::random()))?;
+    let err = allocator.reserve(&client, 1).await.unwrap_err();
+    assert!(!is_unreachable(&err), "{err:#}");
+    assert_eq!(std::fs::read_to_string(&file)?, "44", "Файл не изменился");
+
+    std::fs::remove_file(file)?;
+    Ok(())
+}
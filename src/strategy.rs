@@ -0,0 +1,170 @@
+use eyre::{ensure, eyre, ContextCompat, Result};
+use proptest::{
+    collection::vec,
+    prelude::*,
+    sample::select,
+    test_runner::{Config, TestCaseError, TestRunner},
+};
+use tracing::debug;
+
+use crate::{
+    aptos::AccountAddress,
+    deposit::{balances, expected_deposits, wait_for_deltas},
+    engine_client::{MvEngine, PayloadId, RequestEngine, RequestEvent, RequestSlot, TxDeposit},
+    slot::SlotAllocator,
+    Slot,
+};
+
+/// Сумма одного депозита. Ограничена, чтобы суммы по аккаунту не переполняли `u64`.
+const MAX_AMOUNT: u64 = 1_000_000;
+const MAX_SLOTS: usize = 5;
+const MAX_EVENTS_PER_SLOT: usize = 20;
+
+pub(crate) fn account() -> BoxedStrategy<AccountAddress> {
+    any::<[u8; AccountAddress::LENGTH]>()
+        .prop_map(AccountAddress::new)
+        .boxed()
+}
+
+pub(crate) fn tx_deposit(
+    account: BoxedStrategy<AccountAddress>,
+) -> impl Strategy<Value = TxDeposit> {
+    (account, 0..=MAX_AMOUNT).prop_map(|(account, amount)| TxDeposit { account, amount })
+}
+
+/// Событие, валидность которого не зависит от состояния ноды.
+///
+/// Остальные события содержат идентификаторы (`withdrawal_id`, `nonce`, токен), и при
+/// повторном прогоне во время shrinking нода справедливо отклоняет их как дубликаты.
+pub(crate) fn request_event(
+    account: BoxedStrategy<AccountAddress>,
+) -> impl Strategy<Value = RequestEvent> {
+    tx_deposit(account).prop_map(RequestEvent::Deposit)
+}
+
+/// Слот с номером `0`: номера назначаются в [`request_engine`] и при отправке.
+pub(crate) fn request_slot(
+    account: BoxedStrategy<AccountAddress>,
+) -> impl Strategy<Value = RequestSlot> {
+    vec(request_event(account), 0..=MAX_EVENTS_PER_SLOT)
+        .prop_map(|events| RequestSlot { slot: 0, events })
+}
+
+/// Запрос со слотами `1..=n` и `parent_payload` равным `0`.
+/// Перед отправкой номера сдвигаются через [`rebase`].
+pub(crate) fn request_engine(
+    account: BoxedStrategy<AccountAddress>,
+) -> impl Strategy<Value = RequestEngine> {
+    vec(request_slot(account), 1..=MAX_SLOTS).prop_map(|mut events| {
+        for (index, slot) in events.iter_mut().enumerate() {
+            slot.slot = index as Slot + 1;
+        }
+        RequestEngine {
+            parent_payload: 0,
+            max_payload_size: 1001,
+            events,
+        }
+    })
+}
+
+/// Сдвиг слотов сгенерированного запроса так, чтобы первый слот стал `first_slot`.
+pub(crate) fn rebase(
+    mut request: RequestEngine,
+    first_slot: Slot,
+    parent_payload: PayloadId,
+) -> RequestEngine {
+    for slot in &mut request.events {
+        slot.slot += first_slot - 1;
+    }
+    request.parent_payload = parent_payload;
+    request
+}
+
+/// Отправка сгенерированного запроса и проверка инвариантов: нода применяет все события,
+/// баланс каждого аккаунта растёт на сумму его депозитов, голова сдвигается
+/// не меньше чем на количество слотов.
+pub(crate) async fn check_request<C>(
+    client: &C,
+    aptos_url: &str,
+    slots: &SlotAllocator,
+    request: RequestEngine,
+) -> Result<()>
+where
+    C: MvEngine + Sync,
+{
+    let expected = expected_deposits(&request, None);
+    let info_before = client.engine_l2info_v1().await?;
+    let reserved = slots.reserve(client, request.events.len() as u64).await?;
+    let request = rebase(request, *reserved.start(), info_before.head_payload);
+    let before = balances(aptos_url, expected.keys().copied()).await?;
+
+    let result = client.engine_apply_all(&request).await?;
+    debug!("payload: {}", result.payload_id);
+    let report = wait_for_deltas(aptos_url, &before, &expected).await?;
+    let credited = report.rows.iter().map(|row| row.actual()).sum::<i128>();
+    let deposited = expected.values().sum::<u128>();
+    ensure!(
+        credited == deposited as i128,
+        "Зачислено {credited}, отправлено {deposited}"
+    );
+
+    // Другие тесты могут параллельно отправлять слоты, поэтому сдвиг может быть больше
+    let info_after = client.engine_l2info_v1().await?;
+    info_after.ensure_slot_processed(*reserved.end())?;
+    let shift = info_after
+        .head_slot
+        .checked_sub(info_before.head_slot)
+        .with_context(|| {
+            format!(
+                "head_slot уменьшился {} -> {}. Нода была сброшена во время теста",
+                info_before.head_slot, info_after.head_slot
+            )
+        })?;
+    ensure!(
+        shift >= request.events.len() as u64,
+        "head_slot сдвинулся меньше чем на {} слотов: {} -> {}",
+        request.events.len(),
+        info_before.head_slot,
+        info_after.head_slot
+    );
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn test_request_engine_strategy(request in request_engine(account())) {
+        prop_assert!(request.validate().is_ok());
+        let parsed: RequestEngine =
+            serde_json::from_value(serde_json::to_value(&request).unwrap()).unwrap();
+        prop_assert_eq!(&parsed, &request);
+
+        let rebased = rebase(request, 10, 3);
+        prop_assert_eq!(rebased.events[0].slot, 10);
+        prop_assert!(rebased.validate().is_ok());
+    }
+}
+
+#[test]
+fn test_generated_requests() -> Result<()> {
+    use crate::{aptos::aptos_url, engine_client::new_client, engine_url, jwt::get_jwt, SLOTS};
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let client = new_client(&engine_url(), runtime.block_on(get_jwt()))?;
+    // Свои аккаунты на каждый запуск, чтобы параллельные тесты не влияли на балансы
+    let pool = (0..4)
+        .map(|_| AccountAddress::new(rand::random()))
+        .collect::<Vec<_>>();
+
+    let mut runner = TestRunner::new(Config {
+        cases: 16,
+        failure_persistence: None,
+        ..Config::default()
+    });
+    runner
+        .run(&request_engine(select(pool).boxed()), |request| {
+            runtime
+                .block_on(check_request(&client, &aptos_url(), &SLOTS, request))
+                .map_err(|err| TestCaseError::fail(format!("{err:#}")))
+        })
+        .map_err(|err| eyre!("{err}"))
+}
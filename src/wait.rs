@@ -0,0 +1,354 @@
+use std::{fmt, future::Future, time::Duration};
+
+use eyre::{Result, WrapErr};
+use tokio::time::{sleep, Instant};
+use tracing::debug;
+use tracing_test::traced_test;
+
+use crate::{
+    aptos::{balance_report, AccountAddress, AptosClient, LedgerInfo},
+    engine_client::{L2Info, MvEngine},
+    Slot,
+};
+
+/// Результат одной проверки условия.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Check<T> {
+    Ready(T),
+    /// Условие не выполнено. Строка описывает текущее состояние для диагностики.
+    Pending(String),
+}
+
+/// Параметры ожидания: общий таймаут и пауза между проверками, которая после
+/// каждой проверки растёт в `backoff` раз, но не больше `max_interval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Wait {
+    pub(crate) timeout: Duration,
+    pub(crate) interval: Duration,
+    pub(crate) backoff: u32,
+    pub(crate) max_interval: Duration,
+}
+
+/// Ожидание завершилось по таймауту.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WaitTimeout {
+    pub(crate) what: String,
+    pub(crate) attempts: u32,
+    pub(crate) elapsed: Duration,
+    /// Состояние после первой и последней проверки.
+    pub(crate) first_state: String,
+    pub(crate) last_state: String,
+}
+
+impl fmt::Display for WaitTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Не дождались: {}. Прошло {:?}, проверок: {}.\nПервое состояние: {}\nПоследнее состояние: {}",
+            self.what, self.elapsed, self.attempts, self.first_state, self.last_state
+        )
+    }
+}
+
+impl std::error::Error for WaitTimeout {}
+
+impl Default for Wait {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            interval: Duration::from_millis(100),
+            backoff: 2,
+            max_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Wait {
+    pub(crate) fn timeout(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            ..Self::default()
+        }
+    }
+
+    /// Пауза перед второй проверкой.
+    pub(crate) fn interval(self, interval: Duration) -> Self {
+        Self {
+            interval,
+            max_interval: self.max_interval.max(interval),
+            ..self
+        }
+    }
+
+    /// `backoff` равный 1 - постоянная пауза.
+    pub(crate) fn backoff(self, backoff: u32, max_interval: Duration) -> Self {
+        Self {
+            backoff,
+            max_interval,
+            ..self
+        }
+    }
+
+    /// Пауза после проверки с номером `attempt`, начиная с 1.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        self.interval
+            .saturating_mul(self.backoff.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_interval.max(self.interval))
+    }
+
+    /// Повтор `check`, пока он не вернёт [`Check::Ready`]. По таймауту - [`WaitTimeout`].
+    /// Ошибка `check` прерывает ожидание.
+    pub(crate) async fn until<T, F, Fut>(&self, what: &str, mut check: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Check<T>>>,
+    {
+        let start = Instant::now();
+        let mut first_state = None;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let state = match check().await? {
+                Check::Ready(value) => {
+                    debug!("{what}: {attempts} проверок, {:?}", start.elapsed());
+                    return Ok(value);
+                }
+                Check::Pending(state) => state,
+            };
+
+            let elapsed = start.elapsed();
+            if elapsed >= self.timeout {
+                return Err(WaitTimeout {
+                    what: what.to_string(),
+                    attempts,
+                    elapsed,
+                    first_state: first_state.unwrap_or_else(|| state.clone()),
+                    last_state: state,
+                }
+                .into());
+            }
+            first_state.get_or_insert(state);
+            // Последняя проверка - ровно в момент таймаута
+            sleep(self.delay(attempts).min(self.timeout - elapsed)).await;
+        }
+    }
+
+    /// Повтор `check` в течение всего таймаута, например, чтобы убедиться, что состояние
+    /// не меняется. Первая ошибка `check` прерывает проверку.
+    pub(crate) async fn during<F, Fut>(&self, what: &str, mut check: F) -> Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let start = Instant::now();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            check().await.wrap_err_with(|| {
+                format!("{what}: проверка {attempts}, прошло {:?}", start.elapsed())
+            })?;
+
+            let elapsed = start.elapsed();
+            if elapsed >= self.timeout {
+                debug!("{what}: {attempts} проверок, {elapsed:?}");
+                return Ok(());
+            }
+            sleep(self.delay(attempts).min(self.timeout - elapsed)).await;
+        }
+    }
+}
+
+/// Ожидание, пока баланс `account` станет равен `expected`.
+pub(crate) async fn wait_for_balance(
+    aptos_url: &str,
+    account: &AccountAddress,
+    expected: u64,
+    wait: &Wait,
+) -> Result<()> {
+    wait.until(
+        &format!("баланс {account} равен {expected}"),
+        || async {
+            let report = balance_report(aptos_url, account).await?;
+            report.ensure_consistent(account)?;
+            Ok(if report.total().0 == expected {
+                Check::Ready(())
+            } else {
+                Check::Pending(report.to_string())
+            })
+        },
+    )
+    .await
+}
+
+/// Ожидание, пока Aptos REST API дойдёт до версии `version`.
+pub(crate) async fn wait_for_ledger_version(
+    aptos: &AptosClient,
+    version: u64,
+    wait: &Wait,
+) -> Result<LedgerInfo> {
+    wait.until(&format!("ledger_version {version}"), || async {
+        let info = aptos.ledger_info().await?;
+        Ok(if info.ledger_version >= version {
+            Check::Ready(info)
+        } else {
+            Check::Pending(format!(
+                "ledger_version: {}, block_height: {}",
+                info.ledger_version, info.block_height
+            ))
+        })
+    })
+    .await
+}
+
+/// Ожидание, пока нода обработает слот `slot`.
+pub(crate) async fn wait_for_l2_head<C>(client: &C, slot: Slot, wait: &Wait) -> Result<L2Info>
+where
+    C: MvEngine + Sync,
+{
+    wait.until(&format!("head_slot {slot}"), || async {
+        let info = client.engine_l2info_v1().await?;
+        Ok(if info.head_slot >= slot {
+            Check::Ready(info)
+        } else {
+            Check::Pending(format!("{info:?}"))
+        })
+    })
+    .await
+}
+
+#[test]
+fn test_wait_delay() {
+    let wait = Wait::default();
+    let delays = (1..=6).map(|attempt| wait.delay(attempt).as_millis());
+    assert_eq!(delays.collect::<Vec<_>>(), [100, 200, 400, 800, 1000, 1000]);
+
+    let constant = Wait::default().backoff(1, Duration::ZERO);
+    assert_eq!(constant.delay(10), constant.interval);
+
+    let wide = Wait::default().interval(Duration::from_secs(5));
+    assert_eq!(wide.delay(1), Duration::from_secs(5));
+    assert_eq!(wide.delay(u32::MAX), Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_wait_until() -> Result<()> {
+    let wait = Wait::timeout(Duration::from_millis(200)).interval(Duration::from_millis(1));
+
+    let mut attempts = 0;
+    let value = wait
+        .until("третья проверка", || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                Ok(match attempt {
+                    3 => Check::Ready(attempt),
+                    _ => Check::Pending(format!("проверка {attempt}")),
+                })
+            }
+        })
+        .await?;
+    assert_eq!(value, 3);
+
+    let mut attempts = 0;
+    let err = wait
+        .until("никогда", || {
+            attempts += 1;
+            let attempt = attempts;
+            async move { Ok(Check::<()>::Pending(format!("проверка {attempt}"))) }
+        })
+        .await
+        .unwrap_err();
+    let timeout = err.downcast_ref::<WaitTimeout>().expect("WaitTimeout");
+    assert_eq!(timeout.attempts, attempts);
+    assert_eq!(timeout.first_state, "проверка 1");
+    assert_eq!(timeout.last_state, format!("проверка {attempts}"));
+    assert!(timeout.elapsed >= wait.timeout);
+    assert!(err.to_string().contains("никогда"));
+
+    let err = wait
+        .until("ошибка", || async {
+            eyre::bail!("нода недоступна") as Result<Check<()>>
+        })
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<WaitTimeout>().is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_wait_during() -> Result<()> {
+    let wait = Wait::timeout(Duration::from_millis(50)).interval(Duration::from_millis(1));
+
+    let mut attempts = 0;
+    wait.during("без изменений", || {
+        attempts += 1;
+        async { Ok(()) }
+    })
+    .await?;
+    assert!(attempts > 1);
+
+    let mut attempts = 0;
+    let err = wait
+        .during(
+            "изменение на третьей проверке",
+            || {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    eyre::ensure!(attempt < 3, "изменилось");
+                    Ok(())
+                }
+            },
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(attempts, 3);
+    assert!(format!("{err:#}").contains("проверка 3"), "{err:#}");
+    Ok(())
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_wait_helpers() -> Result<()> {
+    use crate::{
+        aptos::{aptos_url, client, AccountFactory},
+        engine_client::{new_client, RequestEngine},
+        engine_url,
+        jwt::get_jwt,
+        SLOTS,
+    };
+
+    let engine = new_client(&engine_url(), get_jwt().await)?;
+    let aptos = client();
+    let account = AccountFactory::new("wait").account()?.address();
+    let version = aptos.ledger_info().await?.ledger_version;
+
+    let request = RequestEngine::builder()
+        .slot(|slot| slot.deposit(account, 7))
+        .build(&engine, &SLOTS)
+        .await?;
+    engine.engine_apply_all(&request).await?;
+
+    let wait = Wait::default();
+    let slot = request.events[0].slot;
+    assert!(wait_for_l2_head(&engine, slot, &wait).await?.head_slot >= slot);
+    assert!(
+        wait_for_ledger_version(&aptos, version + 1, &wait)
+            .await?
+            .ledger_version
+            > version
+    );
+    wait_for_balance(&aptos_url(), &account, 7, &wait).await?;
+
+    let err = wait_for_balance(
+        &aptos_url(),
+        &account,
+        8,
+        &Wait::timeout(Duration::from_millis(300)),
+    )
+    .await
+    .unwrap_err();
+    let timeout = err.downcast_ref::<WaitTimeout>().expect("WaitTimeout");
+    assert!(timeout.last_state.starts_with("7 "), "{timeout}");
+    Ok(())
+}